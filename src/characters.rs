@@ -20,3 +20,53 @@ pub const ARROW_UP: char = '↑';
 pub const ARROW_DOWN: char = '↓';
 pub const ARROW_LEFT: char = '←';
 pub const ARROW_RIGHT: char = '→';
+
+pub const HEAVY_VERTICAL_BAR: char = '┃';
+pub const HEAVY_HORIZONTAL_BAR: char = '━';
+pub const HEAVY_CORNER_1: char = '┛';
+pub const HEAVY_CORNER_2: char = '┗';
+pub const HEAVY_CORNER_3: char = '┏';
+pub const HEAVY_CORNER_4: char = '┓';
+
+pub const DOUBLE_VERTICAL_BAR: char = '║';
+pub const DOUBLE_HORIZONTAL_BAR: char = '═';
+pub const DOUBLE_CORNER_1: char = '╝';
+pub const DOUBLE_CORNER_2: char = '╚';
+pub const DOUBLE_CORNER_3: char = '╔';
+pub const DOUBLE_CORNER_4: char = '╗';
+
+pub const DASHED_VERTICAL_BAR: char = '┆';
+pub const DASHED_HORIZONTAL_BAR: char = '┄';
+
+pub const ASCII_VERTICAL_BAR: char = '|';
+pub const ASCII_HORIZONTAL_BAR: char = '-';
+pub const ASCII_CORNER: char = '+';
+
+pub const TRIANGLE_UP: char = '▲';
+pub const TRIANGLE_DOWN: char = '▼';
+pub const TRIANGLE_LEFT: char = '◀';
+pub const TRIANGLE_RIGHT: char = '▶';
+
+pub const CHEVRON_UP: char = '^';
+pub const CHEVRON_DOWN: char = 'v';
+pub const CHEVRON_LEFT: char = '<';
+pub const CHEVRON_RIGHT: char = '>';
+
+pub const DIAMOND: char = '◆';
+pub const CIRCLE: char = '○';
+
+pub const INTERSECTION_CROSS: char = '┼';
+
+pub const HEAVY_INTERSECTION_UP: char = '┻';
+pub const HEAVY_INTERSECTION_DOWN: char = '┳';
+pub const HEAVY_INTERSECTION_LEFT: char = '┫';
+pub const HEAVY_INTERSECTION_RIGHT: char = '┣';
+pub const HEAVY_INTERSECTION_CROSS: char = '╋';
+
+pub const DOUBLE_INTERSECTION_UP: char = '╩';
+pub const DOUBLE_INTERSECTION_DOWN: char = '╦';
+pub const DOUBLE_INTERSECTION_LEFT: char = '╣';
+pub const DOUBLE_INTERSECTION_RIGHT: char = '╠';
+pub const DOUBLE_INTERSECTION_CROSS: char = '╬';
+
+pub const OVERFLOW: char = '…';
@@ -0,0 +1,114 @@
+use crate::export::Format;
+
+/// Commands typed after ':' in normal mode
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Go back through the undo history, like vim's `:earlier`
+    Earlier(HistoryStep),
+    /// Go forward through the undo history, like vim's `:later`
+    Later(HistoryStep),
+    /// Save the drawing
+    Write,
+    /// Exit, refusing to when there are unsaved changes unless forced with `:q!`
+    Quit { force: bool },
+    /// Save the drawing and exit
+    WriteQuit,
+    /// Write the drawing in another format next to it, e.g. `:export svg`
+    Export(Format),
+}
+
+/// How far to move through the undo history
+#[derive(Debug, PartialEq)]
+pub enum HistoryStep {
+    Changes(i64),
+    Seconds(i64),
+}
+
+impl Command {
+    pub fn parse(text: &str) -> Option<Command> {
+        let mut words = text.split_whitespace();
+        let name = words.next()?;
+        let argument = words.next();
+        if words.next().is_some() {
+            return None;
+        }
+        match name {
+            "earlier" | "ea" => Some(Command::Earlier(HistoryStep::parse(argument)?)),
+            "later" | "lat" => Some(Command::Later(HistoryStep::parse(argument)?)),
+            "export" => Some(Command::Export(Format::parse(argument?)?)),
+            _ if argument.is_some() => None,
+            "w" | "write" => Some(Command::Write),
+            "q" | "quit" => Some(Command::Quit { force: false }),
+            "q!" | "quit!" => Some(Command::Quit { force: true }),
+            "wq" | "x" => Some(Command::WriteQuit),
+            _ => None,
+        }
+    }
+}
+
+impl HistoryStep {
+    /// A count of changes like `3`, or an amount of time like `10s`, `5m`, `2h` or `1d`
+    fn parse(argument: Option<&str>) -> Option<HistoryStep> {
+        let Some(argument) = argument else {
+            return Some(HistoryStep::Changes(1));
+        };
+        let (count, unit) = match argument.find(|c: char| !c.is_ascii_digit()) {
+            Some(i) => argument.split_at(i),
+            None => (argument, ""),
+        };
+        let count: i64 = count.parse().ok()?;
+        let seconds = match unit {
+            "" => return Some(HistoryStep::Changes(count)),
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => return None,
+        };
+        Some(HistoryStep::Seconds(count.checked_mul(seconds)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::export::Format;
+
+    use super::{Command, HistoryStep};
+
+    #[test]
+    fn should_parse_history_commands() {
+        assert_eq!(
+            Command::parse("earlier 5m"),
+            Some(Command::Earlier(HistoryStep::Seconds(300)))
+        );
+        assert_eq!(
+            Command::parse("lat 3"),
+            Some(Command::Later(HistoryStep::Changes(3)))
+        );
+        assert_eq!(
+            Command::parse("earlier"),
+            Some(Command::Earlier(HistoryStep::Changes(1)))
+        );
+        assert_eq!(Command::parse("earlier 5y"), None);
+        assert_eq!(Command::parse("nonsense"), None);
+        assert_eq!(Command::parse("earlier 999999999999999999d"), None);
+        assert_eq!(
+            Command::parse("later 9223372036854775807"),
+            Some(Command::Later(HistoryStep::Changes(i64::MAX)))
+        );
+    }
+
+    #[test]
+    fn should_parse_file_commands() {
+        assert_eq!(Command::parse("w"), Some(Command::Write));
+        assert_eq!(Command::parse("q"), Some(Command::Quit { force: false }));
+        assert_eq!(Command::parse("q!"), Some(Command::Quit { force: true }));
+        assert_eq!(Command::parse("x"), Some(Command::WriteQuit));
+        assert_eq!(Command::parse("w other.traw"), None);
+        assert_eq!(
+            Command::parse("export svg"),
+            Some(Command::Export(Format::Svg))
+        );
+        assert_eq!(Command::parse("export"), None);
+    }
+}
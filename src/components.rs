@@ -1,6 +1,8 @@
 pub mod arrow;
+pub mod arrow_style;
 pub mod debug_panel;
 pub mod grid_background;
 pub mod intersections;
 pub mod rectangle;
 pub mod status_bar;
+pub mod text_style;
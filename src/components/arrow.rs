@@ -3,23 +3,97 @@ use std::{
     io::Result,
 };
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::{
     characters::{
         ARROW_DOWN, ARROW_LEFT, ARROW_RIGHT, ARROW_UP, CORNER_1, CORNER_2, CORNER_3, CORNER_4,
         HORIZONTAL_BAR, VERTICAL_BAR,
     },
+    components::{
+        arrow_style::{ArrowStyle, HeadPosition},
+        rectangle::{Border, Rectangle},
+    },
     cursor_guide::GuidePoint,
     draw::{Color, CursorIntersect, Draw, EdgeIntersection, Intersection, Point},
+    shape::Translate,
     shape_id::generate_shape_id,
-    util::Vec2,
+    util::{
+        route::route,
+        text::{deserialize_chars, display_width, graphemes, serialize_chars, EditText},
+        Vec2,
+    },
 };
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Arrow {
+    /// The ends and corners of the arrow, every segment between them is a straight horizontal or
+    /// vertical line
+    #[serde(deserialize_with = "deserialize_points")]
     pub points: Vec<Vec2<i32>>,
     pub shape_id: u32,
+    /// Box that the first point is attached to
+    #[serde(default)]
+    pub start: Option<Binding>,
+    /// Box that the last point is attached to
+    #[serde(default)]
+    pub end: Option<Binding>,
+    /// Whether the path is found automatically, instead of being drawn by hand
+    #[serde(default)]
+    pub routed: bool,
+    #[serde(default)]
+    pub style: ArrowStyle,
+    #[serde(default)]
+    pub label: Label,
+}
+
+/// Text written alongside an arrow
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Label {
+    #[serde(
+        serialize_with = "serialize_chars",
+        deserialize_with = "deserialize_chars"
+    )]
+    pub text: Vec<char>,
+    /// Segment the label is placed on, or the middle of the arrow if none is chosen
+    pub segment: Option<usize>,
+}
+
+impl EditText for Label {
+    fn text(&self) -> &Vec<char> {
+        &self.text
+    }
+
+    fn text_mut(&mut self) -> &mut Vec<char> {
+        &mut self.text
+    }
+}
+
+/// Part of an arrow that can be grabbed and dragged, by index into the points
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArrowHandle {
+    Corner(usize),
+    /// The segment between a point and the next one
+    Segment(usize),
+}
+
+/// Where an arrow endpoint is attached to the border of a rectangle
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub shape_id: u32,
+    pub border: Border,
+    /// Distance from the top or left corner of the border
+    pub offset: i32,
+}
+
+impl Binding {
+    pub fn new(rect: &Rectangle, point: &Vec2<i32>) -> Option<Self> {
+        rect.border_at(point).map(|(border, offset)| Self {
+            shape_id: rect.shape_id,
+            border,
+            offset,
+        })
+    }
 }
 
 impl Arrow {
@@ -27,28 +101,360 @@ impl Arrow {
         Self {
             points: vec![],
             shape_id: generate_shape_id(),
+            start: None,
+            end: None,
+            routed: false,
+            style: ArrowStyle::default(),
+            label: Label::default(),
         }
     }
 
-    pub fn update(&mut self, position: Vec2<i32>) {
-        if self.points.len() > 1 && self.points[self.points.len() - 2] == position {
+    /// Where the first character of the label goes. Labels sit above horizontal lines and to the
+    /// right of vertical ones so that they never cover the line.
+    pub fn label_position(&self) -> Option<Vec2<i32>> {
+        let segment = self.label.segment.filter(|i| i + 1 < self.points.len());
+        let (anchor, horizontal) = match segment {
+            Some(i) => {
+                let (a, b) = (&self.points[i], &self.points[i + 1]);
+                (Vec2::new((a.x + b.x) / 2, (a.y + b.y) / 2), a.y == b.y)
+            }
+            None => {
+                let cells = self.cells();
+                if cells.len() < 2 {
+                    return None;
+                }
+                let middle = cells.len() / 2;
+                let horizontal = cells[middle].y == cells[middle - 1].y;
+                (cells[middle].clone(), horizontal)
+            }
+        };
+        Some(match horizontal {
+            true => Vec2::new(
+                anchor.x - display_width(&self.label.text) as i32 / 2,
+                anchor.y - 1,
+            ),
+            false => Vec2::new(anchor.x + 2, anchor.y),
+        })
+    }
+
+    /// Where the cursor is drawn before the character at `index` in the label
+    pub fn get_label_cursor_position(&self, index: usize) -> Option<Vec2<i32>> {
+        let index = index.min(self.label.text.len());
+        self.label_position()
+            .map(|p| p + Vec2::new(display_width(&self.label.text[..index]) as i32, 0))
+    }
+
+    /// Create an arrow that finds its own way between two boxes
+    pub fn route(source: &Rectangle, target: &Rectangle, obstacles: &[&Rectangle]) -> Option<Self> {
+        let mut arrow = Self::init();
+        arrow.points = simplify(&route(source, target, obstacles)?);
+        arrow.routed = true;
+        arrow.bind(&[source, target]);
+        Some(arrow)
+    }
+
+    /// Find a new path between the boxes a routed arrow connects
+    pub fn reroute(&mut self, rectangles: &[&Rectangle]) {
+        let find = |binding: &Option<Binding>| {
+            binding
+                .as_ref()
+                .and_then(|b| rectangles.iter().find(|r| r.shape_id == b.shape_id))
+        };
+        if let (Some(source), Some(target)) = (find(&self.start), find(&self.end)) {
+            if let Some(points) = route(source, target, rectangles) {
+                self.points = simplify(&points);
+                self.bind(&[source, target]);
+            }
+        }
+    }
+
+    /// Attach each endpoint to the border of the rectangle it lies on, if any
+    pub fn bind(&mut self, rectangles: &[&Rectangle]) {
+        let find = |point: Option<&Vec2<i32>>| {
+            point.and_then(|p| rectangles.iter().find_map(|r| Binding::new(r, p)))
+        };
+        self.start = find(self.points.first());
+        self.end = find(self.points.last());
+    }
+
+    pub fn is_bound_to(&self, shape_id: u32) -> bool {
+        [&self.start, &self.end]
+            .iter()
+            .any(|b| b.as_ref().is_some_and(|b| b.shape_id == shape_id))
+    }
+
+    /// Re-route the endpoints attached to a rectangle after it has been moved or resized
+    pub fn follow(&mut self, rect: &Rectangle) {
+        if let Some(binding) = self.end.clone().filter(|b| b.shape_id == rect.shape_id) {
+            self.route_end(
+                rect.border_position(&binding.border, binding.offset),
+                &binding.border,
+            );
+        }
+        if let Some(binding) = self.start.clone().filter(|b| b.shape_id == rect.shape_id) {
+            self.points.reverse();
+            self.route_end(
+                rect.border_position(&binding.border, binding.offset),
+                &binding.border,
+            );
+            self.points.reverse();
+        }
+    }
+
+    /// Remove the endpoints that were attached to a deleted rectangle
+    pub fn detach(&mut self, shape_id: u32) {
+        self.routed = false;
+        if self.end.as_ref().is_some_and(|b| b.shape_id == shape_id) {
+            self.retract_end();
+            self.end = None;
+        }
+        if self.start.as_ref().is_some_and(|b| b.shape_id == shape_id) {
+            self.points.reverse();
+            self.retract_end();
+            self.points.reverse();
+            self.start = None;
+        }
+    }
+
+    /// Move the last point to a new position on a border, shrinking the arrow if it already
+    /// passes by that position, or otherwise extending it so that it enters the border from the
+    /// outside
+    fn route_end(&mut self, anchor: Vec2<i32>, border: &Border) {
+        if self.points.last() == Some(&anchor) {
+            return;
+        }
+        self.retract_end();
+        let approach = anchor.clone() + border.normal();
+        if !self.truncate_at(&approach) {
+            if let Some(current) = self.points.last().cloned() {
+                // Go along the border first, so that the last segment is perpendicular to it
+                match border {
+                    Border::Top | Border::Bottom => self.update(Vec2::new(approach.x, current.y)),
+                    Border::Left | Border::Right => self.update(Vec2::new(current.x, approach.y)),
+                }
+                self.update(approach);
+            }
+        }
+        self.update(anchor);
+    }
+
+    /// Shorten the arrow by one cell at the end
+    fn retract_end(&mut self) {
+        let n = self.points.len();
+        if n < 2 {
+            self.points.pop();
+            return;
+        }
+        let last = self.points[n - 1].clone();
+        let prev = &self.points[n - 2];
+        let retracted = last.clone() + direction(&last, prev);
+        if retracted == *prev {
             self.points.pop();
         } else {
-            self.points.push(position);
+            self.points[n - 1] = retracted;
+        }
+    }
+
+    /// Cut the arrow off at the first place that it passes through a position, returns false if
+    /// the arrow never reaches it
+    fn truncate_at(&mut self, position: &Vec2<i32>) -> bool {
+        if self.points.first() == Some(position) {
+            self.points.truncate(1);
+            return true;
+        }
+        let segment = self
+            .points
+            .windows(2)
+            .position(|w| is_on_segment(&w[0], &w[1], position));
+        match segment {
+            Some(i) => {
+                self.points.truncate(i + 1);
+                self.points.push(position.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Extend the arrow to a new position. Continuing in a straight line lengthens the last
+    /// segment instead of adding a corner, and going back over the last segment shortens it.
+    pub fn update(&mut self, position: Vec2<i32>) {
+        extend_path(&mut self.points, position);
+    }
+
+    /// The corner or segment of the arrow at a position, preferring corners
+    pub fn handle_at(&self, position: &Vec2<i32>) -> Option<ArrowHandle> {
+        if let Some(i) = self.points.iter().position(|p| p == position) {
+            return Some(ArrowHandle::Corner(i));
+        }
+        self.points
+            .windows(2)
+            .position(|w| is_on_segment(&w[0], &w[1], position))
+            .map(ArrowHandle::Segment)
+    }
+
+    /// Move a corner or segment, keeping every segment horizontal or vertical. The ends of the
+    /// arrow are left in place when dragging something next to them, with a new corner added to
+    /// connect to them instead.
+    pub fn drag(&mut self, handle: &ArrowHandle, delta: &Vec2<i32>) {
+        let last = self.points.len().saturating_sub(1);
+        match handle {
+            ArrowHandle::Corner(i) if *i == last => {
+                let moved = self.points[last].clone() + delta.clone();
+                self.points.pop();
+                self.update(moved);
+            }
+            ArrowHandle::Corner(0) => {
+                self.points.reverse();
+                self.drag(&ArrowHandle::Corner(last), delta);
+                self.points.reverse();
+            }
+            ArrowHandle::Corner(i) => {
+                // The segment after the corner goes first, since the one before can insert a
+                // point at the start which shifts the indices
+                self.move_segment(*i, delta);
+                self.move_segment(*i - 1, delta);
+            }
+            ArrowHandle::Segment(i) => self.move_segment(*i, delta),
+        }
+        self.points = simplify(&self.points);
+    }
+
+    /// Move a segment sideways, ignoring the part of the delta that runs along it
+    fn move_segment(&mut self, i: usize, delta: &Vec2<i32>) {
+        let (Some(a), Some(b)) = (self.points.get(i), self.points.get(i + 1)) else {
+            return;
+        };
+        let shift = match (a.x == b.x, a.y == b.y) {
+            (false, true) => Vec2::new(0, delta.y),
+            (true, false) => Vec2::new(delta.x, 0),
+            _ => return,
+        };
+        let mut i = i;
+        if i + 2 == self.points.len() {
+            self.points.push(self.points[i + 1].clone());
+        }
+        if i == 0 {
+            self.points.insert(0, self.points[0].clone());
+            i += 1;
+        }
+        self.points[i] = self.points[i].clone() + shift.clone();
+        self.points[i + 1] = self.points[i + 1].clone() + shift;
+    }
+
+    /// Split a segment at a position so that half of it can be dragged out into a step. Returns
+    /// the half that should be dragged.
+    pub fn insert_bend(&mut self, position: &Vec2<i32>) -> Option<ArrowHandle> {
+        let Some(ArrowHandle::Segment(i)) = self.handle_at(position) else {
+            return None;
+        };
+        self.points.insert(i + 1, position.clone());
+        self.points.insert(i + 1, position.clone());
+        Some(ArrowHandle::Segment(i + 2))
+    }
+
+    /// Take out a corner. The corner next to it slides along its other segment to line up with the
+    /// one before, which straightens out steps. When both neighbours are the ends of the arrow, the
+    /// end that is not attached to a box is dropped instead, leaving the corner as the new end
+    pub fn remove_bend(&mut self, i: usize) {
+        let last = self.points.len().saturating_sub(1);
+        if i == 0 || i >= last {
+            return;
+        }
+        let prev = self.points[i - 1].clone();
+        let next = self.points[i + 1].clone();
+        if i + 1 < last {
+            let after = &self.points[i + 2];
+            self.points[i + 1] = match next.y == after.y {
+                true => Vec2::new(prev.x, next.y),
+                false => Vec2::new(next.x, prev.y),
+            };
+        } else if i > 1 {
+            let before = &self.points[i - 2];
+            self.points[i - 1] = match prev.y == before.y {
+                true => Vec2::new(next.x, prev.y),
+                false => Vec2::new(prev.x, next.y),
+            };
+        } else if self.end.is_none() {
+            self.points.truncate(last);
+            self.points = simplify(&self.points);
+            return;
+        } else if self.start.is_none() {
+            self.points.remove(0);
+            self.points = simplify(&self.points);
+            return;
+        } else {
+            // Both ends are attached, so they can only be joined around a corner
+            return;
+        }
+        self.points.remove(i);
+        self.points = simplify(&self.points);
+    }
+
+    /// Point the arrow the other way
+    pub fn reverse(&mut self) {
+        self.points.reverse();
+        std::mem::swap(&mut self.start, &mut self.end);
+    }
+
+    /// Every cell that the arrow passes through, in order
+    pub fn cells(&self) -> Vec<Vec2<i32>> {
+        let mut cells: Vec<Vec2<i32>> = self.points.first().cloned().into_iter().collect();
+        for w in self.points.windows(2) {
+            let mut current = w[0].clone();
+            let target = &w[1];
+            while current != *target {
+                match current.x != target.x {
+                    true => current.x += (target.x - current.x).signum(),
+                    false => current.y += (target.y - current.y).signum(),
+                }
+                cells.push(current.clone());
+            }
         }
+        cells
     }
 
     const FG: Color = Color::Border;
     const BG: Color = Color::BorderBackground;
 
-    fn get_endpoint(&self, point: &Vec2<i32>, neighbor: &Vec2<i32>) -> Result<Point<i32>> {
-        self.get_point(
-            point,
-            match point.y != neighbor.y && point.x == neighbor.x {
-                true => VERTICAL_BAR,
-                false => HORIZONTAL_BAR,
-            },
-        )
+    fn get_endpoint(point: &Vec2<i32>, neighbor: &Vec2<i32>) -> char {
+        match point.y != neighbor.y && point.x == neighbor.x {
+            true => VERTICAL_BAR,
+            false => HORIZONTAL_BAR,
+        }
+    }
+
+    /// Cells that are drawn as a head instead of a line. A head at an end that is attached to a
+    /// box is drawn one cell out, so that it does not cover the border.
+    fn heads(&self, cells: &[Vec2<i32>]) -> Vec<(usize, char)> {
+        let mut heads = vec![];
+        let n = cells.len();
+        if n < 2 {
+            return heads;
+        }
+        if self.style.head.at_end() {
+            let i = if self.end.is_some() && n > 2 {
+                n - 2
+            } else {
+                n - 1
+            };
+            heads.push((i, Self::get_head(&cells[i - 1], &cells[i])));
+        }
+        if self.style.head.at_start() {
+            let i = if self.start.is_some() && n > 2 { 1 } else { 0 };
+            heads.push((i, Self::get_head(&cells[i + 1], &cells[i])));
+        }
+        heads
+    }
+
+    /// Arrow character pointing from one cell to the next
+    fn get_head(from: &Vec2<i32>, to: &Vec2<i32>) -> char {
+        match (to.x.cmp(&from.x), to.y.cmp(&from.y)) {
+            (_, Less) => ARROW_UP,
+            (_, Greater) => ARROW_DOWN,
+            (Less, _) => ARROW_LEFT,
+            _ => ARROW_RIGHT,
+        }
     }
 
     fn get_point(&self, Vec2 { x, y }: &Vec2<i32>, c: char) -> Result<Point<i32>> {
@@ -90,48 +496,142 @@ impl Arrow {
     }
 }
 
+/// Unit step from one position towards another
+fn direction(from: &Vec2<i32>, to: &Vec2<i32>) -> Vec2<i32> {
+    Vec2::new((to.x - from.x).signum(), (to.y - from.y).signum())
+}
+
+fn is_on_segment(a: &Vec2<i32>, b: &Vec2<i32>, p: &Vec2<i32>) -> bool {
+    (p.x >= a.x.min(b.x) && p.x <= a.x.max(b.x))
+        && (p.y >= a.y.min(b.y) && p.y <= a.y.max(b.y))
+        && (a.x == b.x || a.y == b.y)
+}
+
+/// Add a position to the end of a list of corners, see [Arrow::update]
+fn extend_path(points: &mut Vec<Vec2<i32>>, position: Vec2<i32>) {
+    let Some(last) = points.last().cloned() else {
+        points.push(position);
+        return;
+    };
+    if position == last {
+        return;
+    }
+    if position.x != last.x && position.y != last.y {
+        // Segments can only be straight lines, so go around the corner
+        extend_path(points, Vec2::new(position.x, last.y));
+        extend_path(points, position);
+        return;
+    }
+    let n = points.len();
+    if n > 1 {
+        let prev = points[n - 2].clone();
+        let current_direction = direction(&prev, &last);
+        let next_direction = direction(&last, &position);
+        if next_direction == current_direction {
+            points[n - 1] = position;
+            return;
+        }
+        if next_direction == -current_direction.clone() {
+            if direction(&prev, &position) == current_direction {
+                points[n - 1] = position;
+            } else {
+                // Went all the way back past the previous corner
+                points.pop();
+                extend_path(points, position);
+            }
+            return;
+        }
+    }
+    points.push(position);
+}
+
+/// Reduce a list of points to the corners of the path it describes, removing repeated points and
+/// points in the middle of straight lines
+fn simplify(points: &[Vec2<i32>]) -> Vec<Vec2<i32>> {
+    let mut simplified = vec![];
+    points
+        .iter()
+        .for_each(|p| extend_path(&mut simplified, p.clone()));
+    simplified
+}
+
+/// Files saved before arrows were made of corners have a point for every cell
+fn deserialize_points<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<Vec2<i32>>, D::Error> {
+    Vec::<Vec2<i32>>::deserialize(deserializer).map(|points| simplify(&points))
+}
+
 impl Draw for Arrow {
     fn draw(&self) -> std::io::Result<Vec<Point<i32>>> {
         let mut points = vec![];
         let mut add_arrow = false;
-        self.points
+        let cells = self.cells();
+        let heads = self.heads(&cells);
+        cells
             .iter()
             .enumerate()
             .map(|(i, point)| {
                 let prev = match i {
                     0 => None,
-                    n => self.points.get(n - 1),
+                    n => cells.get(n - 1),
                 };
-                let next = self.points.get(i + 1);
-                if i == self.points.len() / 2 {
+                let next = cells.get(i + 1);
+                if i == cells.len() / 2 && self.style.head == HeadPosition::Middle {
                     add_arrow = true;
                 }
-                match (prev, next) {
-                    (None, Some(p)) | (Some(p), None) => points.push(self.get_endpoint(point, p)?),
-                    (Some(p), Some(n)) => {
-                        points.push(
-                            self.get_point(point, Self::get_char(p, point, n, &mut add_arrow))?,
-                        );
-                    }
-                    _ => {}
+                let character = match (prev, next) {
+                    (None, Some(p)) | (Some(p), None) => Self::get_endpoint(point, p),
+                    (Some(p), Some(n)) => Self::get_char(p, point, n, &mut add_arrow),
+                    _ => return Result::Ok(()),
                 };
+                let character = heads
+                    .iter()
+                    .find(|(j, _)| *j == i)
+                    .map_or(character, |(_, head)| *head);
+                points.push(self.get_point(point, self.style.apply(character))?);
                 Result::Ok(())
             })
             .collect::<Result<Vec<_>>>()?;
+        if let Some(start) = self.label_position() {
+            let mut x = 0;
+            for (range, width) in graphemes(&self.label.text) {
+                let position = start.clone() + Vec2::new(x, 0);
+                x += width as i32;
+                // The arrow can turn back on itself, in which case the line wins
+                let hits_line = (0..width.max(1) as i32)
+                    .any(|i| cells.contains(&(position.clone() + Vec2::new(i, 0))));
+                if hits_line {
+                    continue;
+                }
+                for c in &self.label.text[range] {
+                    points.push(self.get_point(&position, *c)?);
+                }
+            }
+        }
 
         Ok(points)
     }
 }
 
+impl Translate for Arrow {
+    fn translate(&mut self, delta: &Vec2<i32>) {
+        self.points
+            .iter_mut()
+            .for_each(|p| *p = p.clone() + delta.clone());
+    }
+}
+
 impl CursorIntersect for Arrow {
     fn get_intersection(&self, p: &Vec2<i32>) -> crate::draw::Intersection {
-        for point in &self.points {
-            if point != p {
-                continue;
-            }
-            return Intersection::Edge(EdgeIntersection::Side);
+        let on_arrow = match self.points.as_slice() {
+            [point] => point == p,
+            points => points.windows(2).any(|w| is_on_segment(&w[0], &w[1], p)),
+        };
+        match on_arrow {
+            true => Intersection::Edge(EdgeIntersection::Side),
+            false => Intersection::None,
         }
-        Intersection::None
     }
 }
 
@@ -147,9 +647,17 @@ impl GuidePoint for Arrow {
 
 #[cfg(test)]
 mod test {
-    use crate::{characters::*, util::Vec2};
+    use crate::{
+        characters::*,
+        components::{
+            arrow_style::{HeadPosition, LineWeight},
+            rectangle::Rectangle,
+        },
+        draw::Draw,
+        util::Vec2,
+    };
 
-    use super::Arrow;
+    use super::{simplify, Arrow, ArrowHandle};
 
     #[test]
     fn should_get_horizontal_bar() {
@@ -255,4 +763,200 @@ mod test {
         let expected = vec![Vec2::new(0, 0), Vec2::new(1, 0)];
         assert_eq!(arrow.points, expected);
     }
+
+    #[test]
+    fn should_extend_straight_segment_for_count_motions() {
+        let mut arrow = Arrow::init();
+        arrow.update((0, 0).into());
+        arrow.update((1, 0).into());
+        arrow.update((10, 0).into());
+        arrow.update((10, 4).into());
+        assert_eq!(
+            arrow.points,
+            vec![Vec2::new(0, 0), Vec2::new(10, 0), Vec2::new(10, 4)]
+        );
+        assert_eq!(arrow.cells().len(), 15);
+    }
+
+    #[test]
+    fn should_convert_cell_points_to_corners() {
+        let points = vec![
+            Vec2::new(0, 0),
+            Vec2::new(1, 0),
+            Vec2::new(1, 0),
+            Vec2::new(2, 0),
+            Vec2::new(2, 1),
+            Vec2::new(2, 2),
+        ];
+        assert_eq!(
+            simplify(&points),
+            vec![Vec2::new(0, 0), Vec2::new(2, 0), Vec2::new(2, 2)]
+        );
+    }
+
+    #[test]
+    fn should_draw_corner_between_segments() {
+        let mut arrow = Arrow::init();
+        arrow.points = vec![Vec2::new(0, 0), Vec2::new(3, 0), Vec2::new(3, 2)];
+        let points = arrow.draw().unwrap();
+        let corner = points.iter().find(|p| p.origin == Vec2::new(3, 0)).unwrap();
+        assert_eq!(corner.character, CORNER_4);
+        assert_eq!(points.len(), 6);
+    }
+
+    #[test]
+    fn should_draw_heads_outside_of_bound_box() {
+        let (mut arrow, _) = bound_arrow();
+        arrow.style.head = HeadPosition::Both;
+        arrow.style.line = LineWeight::Heavy;
+        let characters: String = arrow.draw().unwrap().iter().map(|p| p.character).collect();
+        assert_eq!(
+            characters,
+            format!("{ARROW_LEFT}{HEAVY_HORIZONTAL_BAR}{ARROW_RIGHT}{HEAVY_HORIZONTAL_BAR}")
+        );
+    }
+
+    #[test]
+    fn should_place_label_beside_line() {
+        let mut arrow = l_arrow();
+        arrow.label.text = vec!['a', 'b'];
+        arrow.label.segment = Some(0);
+        assert_eq!(arrow.label_position(), Some(Vec2::new(1, -1)));
+        arrow.label.segment = Some(1);
+        assert_eq!(arrow.label_position(), Some(Vec2::new(7, 2)));
+        let points = arrow.draw().unwrap();
+        let label = points.iter().find(|p| p.character == 'b').unwrap();
+        assert_eq!(label.origin, Vec2::new(8, 2));
+    }
+
+    fn bound_arrow() -> (Arrow, Rectangle) {
+        let mut rect = Rectangle::new_at(3, 0);
+        rect.width = 3;
+        rect.height = 5;
+        let mut arrow = Arrow::init();
+        arrow.points = vec![Vec2::new(0, 2), Vec2::new(3, 2)];
+        arrow.bind(&[&rect]);
+        (arrow, rect)
+    }
+
+    #[test]
+    fn should_extend_arrow_when_box_moves_away() {
+        let (mut arrow, mut rect) = bound_arrow();
+        rect.origin.x += 2;
+        arrow.follow(&rect);
+        assert_eq!(arrow.points, vec![Vec2::new(0, 2), Vec2::new(5, 2)]);
+    }
+
+    #[test]
+    fn should_shrink_arrow_when_box_moves_closer() {
+        let (mut arrow, mut rect) = bound_arrow();
+        rect.origin.x -= 1;
+        arrow.follow(&rect);
+        assert_eq!(arrow.points, vec![Vec2::new(0, 2), Vec2::new(2, 2)]);
+    }
+
+    #[test]
+    fn should_enter_border_perpendicular_when_box_moves_sideways() {
+        let (mut arrow, mut rect) = bound_arrow();
+        rect.origin.y += 1;
+        arrow.follow(&rect);
+        let expected = vec![
+            Vec2::new(0, 2),
+            Vec2::new(2, 2),
+            Vec2::new(2, 3),
+            Vec2::new(3, 3),
+        ];
+        assert_eq!(arrow.points, expected);
+    }
+
+    #[test]
+    fn should_remove_endpoint_when_box_is_deleted() {
+        let (mut arrow, rect) = bound_arrow();
+        arrow.detach(rect.shape_id);
+        assert_eq!(arrow.points, vec![Vec2::new(0, 2), Vec2::new(2, 2)]);
+        assert!(arrow.end.is_none());
+    }
+
+    fn l_arrow() -> Arrow {
+        let mut arrow = Arrow::init();
+        arrow.points = vec![Vec2::new(0, 0), Vec2::new(5, 0), Vec2::new(5, 5)];
+        arrow
+    }
+
+    #[test]
+    fn should_add_corner_when_dragging_segment_next_to_end() {
+        let mut arrow = l_arrow();
+        arrow.drag(&ArrowHandle::Segment(1), &(2, 1).into());
+        let expected = vec![
+            Vec2::new(0, 0),
+            Vec2::new(7, 0),
+            Vec2::new(7, 5),
+            Vec2::new(5, 5),
+        ];
+        assert_eq!(arrow.points, expected);
+    }
+
+    #[test]
+    fn should_drag_corner_in_both_directions() {
+        let mut arrow = l_arrow();
+        arrow.drag(&ArrowHandle::Corner(1), &(1, 1).into());
+        let expected = vec![
+            Vec2::new(0, 0),
+            Vec2::new(0, 1),
+            Vec2::new(6, 1),
+            Vec2::new(6, 5),
+            Vec2::new(5, 5),
+        ];
+        assert_eq!(arrow.points, expected);
+    }
+
+    #[test]
+    fn should_insert_bend_that_drags_into_step() {
+        let mut arrow = Arrow::init();
+        arrow.points = vec![Vec2::new(0, 0), Vec2::new(10, 0)];
+        let handle = arrow.insert_bend(&(4, 0).into()).unwrap();
+        arrow.drag(&handle, &(0, 2).into());
+        let expected = vec![
+            Vec2::new(0, 0),
+            Vec2::new(4, 0),
+            Vec2::new(4, 2),
+            Vec2::new(10, 2),
+            Vec2::new(10, 0),
+        ];
+        assert_eq!(arrow.points, expected);
+    }
+
+    #[test]
+    fn should_straighten_step_when_removing_bend() {
+        let mut arrow = Arrow::init();
+        arrow.points = vec![
+            Vec2::new(0, 0),
+            Vec2::new(5, 0),
+            Vec2::new(5, 2),
+            Vec2::new(10, 2),
+        ];
+        arrow.remove_bend(1);
+        assert_eq!(
+            arrow.points,
+            vec![Vec2::new(0, 0), Vec2::new(0, 2), Vec2::new(10, 2)]
+        );
+    }
+
+    #[test]
+    fn should_remove_only_bend() {
+        let mut arrow = Arrow::init();
+        arrow.points = vec![Vec2::new(0, 0), Vec2::new(5, 0), Vec2::new(5, 2)];
+        arrow.remove_bend(1);
+        assert_eq!(arrow.points, vec![Vec2::new(0, 0), Vec2::new(5, 0)]);
+
+        // The end of an arrow attached to a box stays where it is
+        let mut rect = Rectangle::new_at(3, 3);
+        rect.width = 5;
+        rect.height = 3;
+        let mut arrow = Arrow::init();
+        arrow.points = vec![Vec2::new(0, 0), Vec2::new(5, 0), Vec2::new(5, 3)];
+        arrow.bind(&[&rect]);
+        arrow.remove_bend(1);
+        assert_eq!(arrow.points, vec![Vec2::new(5, 0), Vec2::new(5, 3)]);
+    }
 }
@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+
+use crate::characters::*;
+
+/// How an arrow is drawn. Arrows are laid out with light lines and arrow characters, which are
+/// then swapped for the characters of the style.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ArrowStyle {
+    #[serde(default)]
+    pub head: HeadPosition,
+    #[serde(default)]
+    pub glyphs: HeadGlyphs,
+    #[serde(default)]
+    pub line: LineWeight,
+    /// Jump over lines that the arrow crosses, instead of joining them
+    #[serde(default)]
+    pub hop: bool,
+}
+
+impl ArrowStyle {
+    pub fn apply(&self, c: char) -> char {
+        match c {
+            ARROW_UP | ARROW_DOWN | ARROW_LEFT | ARROW_RIGHT => self.glyphs.get(c),
+            c => self.line.get(c),
+        }
+    }
+}
+
+/// Where along an arrow the heads are drawn
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum HeadPosition {
+    #[default]
+    Middle,
+    End,
+    Start,
+    Both,
+    None,
+}
+
+impl HeadPosition {
+    pub fn next(&self) -> Self {
+        match self {
+            HeadPosition::Middle => HeadPosition::End,
+            HeadPosition::End => HeadPosition::Start,
+            HeadPosition::Start => HeadPosition::Both,
+            HeadPosition::Both => HeadPosition::None,
+            HeadPosition::None => HeadPosition::Middle,
+        }
+    }
+
+    pub fn at_start(&self) -> bool {
+        matches!(self, HeadPosition::Start | HeadPosition::Both)
+    }
+
+    pub fn at_end(&self) -> bool {
+        matches!(self, HeadPosition::End | HeadPosition::Both)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum HeadGlyphs {
+    #[default]
+    Arrow,
+    Triangle,
+    Ascii,
+    Diamond,
+    Circle,
+}
+
+impl HeadGlyphs {
+    pub fn next(&self) -> Self {
+        match self {
+            HeadGlyphs::Arrow => HeadGlyphs::Triangle,
+            HeadGlyphs::Triangle => HeadGlyphs::Ascii,
+            HeadGlyphs::Ascii => HeadGlyphs::Diamond,
+            HeadGlyphs::Diamond => HeadGlyphs::Circle,
+            HeadGlyphs::Circle => HeadGlyphs::Arrow,
+        }
+    }
+
+    /// The head in this set pointing the same way as one of the arrow characters
+    fn get(&self, arrow: char) -> char {
+        let [up, down, left, right] = match self {
+            HeadGlyphs::Arrow => [ARROW_UP, ARROW_DOWN, ARROW_LEFT, ARROW_RIGHT],
+            HeadGlyphs::Triangle => [TRIANGLE_UP, TRIANGLE_DOWN, TRIANGLE_LEFT, TRIANGLE_RIGHT],
+            HeadGlyphs::Ascii => [CHEVRON_UP, CHEVRON_DOWN, CHEVRON_LEFT, CHEVRON_RIGHT],
+            HeadGlyphs::Diamond => [DIAMOND; 4],
+            HeadGlyphs::Circle => [CIRCLE; 4],
+        };
+        match arrow {
+            ARROW_UP => up,
+            ARROW_DOWN => down,
+            ARROW_LEFT => left,
+            _ => right,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum LineWeight {
+    #[default]
+    Light,
+    Heavy,
+    Double,
+    Dashed,
+    Ascii,
+}
+
+impl LineWeight {
+    pub fn next(&self) -> Self {
+        match self {
+            LineWeight::Light => LineWeight::Heavy,
+            LineWeight::Heavy => LineWeight::Double,
+            LineWeight::Double => LineWeight::Dashed,
+            LineWeight::Dashed => LineWeight::Ascii,
+            LineWeight::Ascii => LineWeight::Light,
+        }
+    }
+
+    /// The character in this weight with the same shape as a light line character
+    pub fn get(&self, light: char) -> char {
+        let [horizontal, vertical, corner_1, corner_2, corner_3, corner_4] = match self {
+            LineWeight::Light => return light,
+            LineWeight::Heavy => [
+                HEAVY_HORIZONTAL_BAR,
+                HEAVY_VERTICAL_BAR,
+                HEAVY_CORNER_1,
+                HEAVY_CORNER_2,
+                HEAVY_CORNER_3,
+                HEAVY_CORNER_4,
+            ],
+            LineWeight::Double => [
+                DOUBLE_HORIZONTAL_BAR,
+                DOUBLE_VERTICAL_BAR,
+                DOUBLE_CORNER_1,
+                DOUBLE_CORNER_2,
+                DOUBLE_CORNER_3,
+                DOUBLE_CORNER_4,
+            ],
+            LineWeight::Dashed => [
+                DASHED_HORIZONTAL_BAR,
+                DASHED_VERTICAL_BAR,
+                CORNER_1,
+                CORNER_2,
+                CORNER_3,
+                CORNER_4,
+            ],
+            LineWeight::Ascii => [
+                ASCII_HORIZONTAL_BAR,
+                ASCII_VERTICAL_BAR,
+                ASCII_CORNER,
+                ASCII_CORNER,
+                ASCII_CORNER,
+                ASCII_CORNER,
+            ],
+        };
+        match light {
+            HORIZONTAL_BAR => horizontal,
+            VERTICAL_BAR => vertical,
+            CORNER_1 => corner_1,
+            CORNER_2 => corner_2,
+            CORNER_3 => corner_3,
+            CORNER_4 => corner_4,
+            c => c,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::characters::*;
+
+    use super::{ArrowStyle, HeadGlyphs, LineWeight};
+
+    #[test]
+    fn should_swap_characters_for_style() {
+        let style = ArrowStyle {
+            glyphs: HeadGlyphs::Triangle,
+            line: LineWeight::Double,
+            ..Default::default()
+        };
+        assert_eq!(style.apply(ARROW_RIGHT), TRIANGLE_RIGHT);
+        assert_eq!(style.apply(CORNER_3), DOUBLE_CORNER_3);
+        assert_eq!(style.apply(VERTICAL_BAR), DOUBLE_VERTICAL_BAR);
+    }
+}
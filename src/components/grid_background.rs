@@ -1,34 +1,40 @@
-use crossterm::terminal;
-
 use crate::{
     draw::{Color, Draw, Point},
     util::Vec2,
+    viewport::Viewport,
 };
 
-pub struct GridBackground {}
+pub struct GridBackground {
+    origin: Vec2<i32>,
+    width: i32,
+    height: i32,
+}
 
 impl GridBackground {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(viewport: &Viewport) -> Self {
+        let (width, height) = viewport.size();
+        Self {
+            origin: viewport.offset.clone(),
+            width: width as i32,
+            height: height as i32,
+        }
     }
 }
 
 impl Draw for GridBackground {
     fn draw(&self) -> std::io::Result<Vec<crate::draw::Point<i32>>> {
-        let (w, h) = terminal::size()?;
         let mut points = vec![];
-        for x in 0..w {
-            for y in 0..h {
-                if (x % 12 == 0 && y % 6 == 0) || ((x + 6) % 12 == 0 && (y + 3) % 6 == 0) {
-                    points.push(
-                        Point {
-                            origin: Vec2 { x, y },
-                            character: '*',
-                            foreground: Color::Grid,
-                            background: Color::EmptyBackground,
-                        }
-                        .into(),
-                    )
+        for x in self.origin.x..self.origin.x + self.width {
+            for y in self.origin.y..self.origin.y + self.height {
+                if (x.rem_euclid(12) == 0 && y.rem_euclid(6) == 0)
+                    || ((x + 6).rem_euclid(12) == 0 && (y + 3).rem_euclid(6) == 0)
+                {
+                    points.push(Point {
+                        origin: Vec2 { x, y },
+                        character: '*',
+                        foreground: Color::Grid,
+                        background: Color::EmptyBackground,
+                    })
                 }
             }
         }
@@ -1,12 +1,38 @@
+use std::collections::HashMap;
+
 use crate::{
-    characters::{INTERSECTION_DOWN, INTERSECTION_LEFT, INTERSECTION_RIGHT, INTERSECTION_UP},
+    characters::*,
+    components::arrow_style::LineWeight,
     draw::{Color, CursorIntersect, Draw, Intersection, Point},
-    mode::Mode,
+    mode::{Mode, Routing},
     shape::Shape,
     state::State,
     util::Vec2,
 };
 
+/// Directions that lines leave a cell in, combined as bit flags
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+const WEIGHTS: [LineWeight; 5] = [
+    LineWeight::Light,
+    LineWeight::Heavy,
+    LineWeight::Double,
+    LineWeight::Dashed,
+    LineWeight::Ascii,
+];
+
+/// Part of a line passing through a cell
+struct Connection {
+    directions: u8,
+    weight: LineWeight,
+    hop: bool,
+}
+
+/// Where the lines of different shapes meet, the characters are replaced with the box drawing
+/// junction that joins all of them
 pub struct Intersections {
     points: Vec<Point<i32>>,
 }
@@ -15,7 +41,7 @@ impl Intersections {
     pub fn new(state: &State) -> Self {
         let mut all_arrows = vec![];
         let mut all_rectangles = vec![];
-        state.shapes.iter().for_each(|s| match s {
+        state.document.shapes.iter().for_each(|s| match s {
             Shape::Rectangle(r) => all_rectangles.push(r),
             Shape::Arrow(a) => all_arrows.push(a),
         });
@@ -23,55 +49,83 @@ impl Intersections {
             Mode::DrawRectangle(rect, _) => {
                 all_rectangles.push(rect);
             }
-            Mode::Text(rect) => {
-                all_rectangles.push(rect);
+            Mode::Text(edit) => {
+                all_rectangles.push(&edit.target);
             }
-            Mode::DrawArrow(arrow) => {
+            Mode::DrawArrow(arrow)
+            | Mode::Route(Routing {
+                preview: Some(arrow),
+                ..
+            }) => {
                 all_arrows.push(arrow);
             }
             _ => {}
         }
-        let mut intersection_points = vec![];
-        let mut add_intersection_point =
-            |point: Option<&Vec2<i32>>, reference: Option<&Vec2<i32>>| {
-                if let Some(p) = point {
-                    all_rectangles.iter().for_each(|r| {
-                        let intersection = r.get_intersection(p);
-                        if let Intersection::Edge(_) = intersection {
-                            if let Some(r) = reference {
-                                let character = {
-                                    if r.x > p.x {
-                                        INTERSECTION_RIGHT
-                                    } else if r.x < p.x {
-                                        INTERSECTION_LEFT
-                                    } else if r.y < p.y {
-                                        INTERSECTION_UP
-                                    } else if r.y > p.y {
-                                        INTERSECTION_DOWN
-                                    } else {
-                                        unreachable!("Reference point should always be different than endpoint")
-                                    }
-                                };
-                                intersection_points.push(Point {
-                                    origin: Vec2 { x: p.x, y: p.y, },
-                                    character,
-                                    foreground: Color::Border,
-                                    background: Color::BorderBackground,
-                                });
-                            }
-                        }
-                    })
+
+        let mut connections: HashMap<Vec2<i32>, Vec<Connection>> = HashMap::new();
+        for rect in all_rectangles {
+            for point in rect.draw().unwrap_or_default() {
+                // Text inside of the box can contain line characters too
+                if !matches!(rect.get_intersection(&point), Intersection::Edge(_)) {
+                    continue;
+                }
+                if let Some((directions, weight)) = get_directions(point.character) {
+                    connections
+                        .entry(point.origin)
+                        .or_default()
+                        .push(Connection {
+                            directions,
+                            weight,
+                            hop: false,
+                        });
                 }
-            };
-        all_arrows.iter().for_each(|a| {
-            add_intersection_point(a.points.first(), a.points.get(1));
-            if a.points.len() > 1 {
-                add_intersection_point(a.points.last(), a.points.get(a.points.len() - 2));
             }
-        });
-        Self {
-            points: intersection_points,
         }
+        for arrow in all_arrows {
+            let cells = arrow.cells();
+            if cells.len() < 2 {
+                continue;
+            }
+            // Arrows draw one point for each cell, in order, before their label
+            let drawn = arrow.draw().unwrap_or_default();
+            for (i, cell) in cells.iter().enumerate() {
+                // Heads are left alone
+                if drawn
+                    .get(i)
+                    .is_none_or(|p| get_directions(p.character).is_none())
+                {
+                    continue;
+                }
+                let neighbors = [
+                    i.checked_sub(1).and_then(|j| cells.get(j)),
+                    cells.get(i + 1),
+                ];
+                let directions = neighbors
+                    .into_iter()
+                    .flatten()
+                    .fold(0, |d, n| d | direction_to(cell, n));
+                connections
+                    .entry(cell.clone())
+                    .or_default()
+                    .push(Connection {
+                        directions,
+                        weight: arrow.style.line.clone(),
+                        hop: arrow.style.hop,
+                    });
+            }
+        }
+
+        let points = connections
+            .into_iter()
+            .filter(|(_, c)| c.len() > 1)
+            .map(|(origin, c)| Point {
+                origin,
+                character: get_junction(&c),
+                foreground: Color::Border,
+                background: Color::BorderBackground,
+            })
+            .collect();
+        Self { points }
     }
 }
 
@@ -80,3 +134,200 @@ impl Draw for Intersections {
         Ok(self.points.clone())
     }
 }
+
+fn direction_to(from: &Vec2<i32>, to: &Vec2<i32>) -> u8 {
+    if to.y < from.y {
+        UP
+    } else if to.y > from.y {
+        DOWN
+    } else if to.x < from.x {
+        LEFT
+    } else {
+        RIGHT
+    }
+}
+
+/// Character for a set of line directions, indexed by their flags. Lines that only go one way
+/// from the cell are drawn straight through it
+fn get_glyphs(weight: &LineWeight) -> [char; 16] {
+    let [vertical, horizontal, corner_1, corner_2, corner_3, corner_4, up, down, left, right, cross] =
+        match weight {
+            LineWeight::Light | LineWeight::Dashed => [
+                VERTICAL_BAR,
+                HORIZONTAL_BAR,
+                CORNER_1,
+                CORNER_2,
+                CORNER_3,
+                CORNER_4,
+                INTERSECTION_UP,
+                INTERSECTION_DOWN,
+                INTERSECTION_LEFT,
+                INTERSECTION_RIGHT,
+                INTERSECTION_CROSS,
+            ],
+            LineWeight::Heavy => [
+                HEAVY_VERTICAL_BAR,
+                HEAVY_HORIZONTAL_BAR,
+                HEAVY_CORNER_1,
+                HEAVY_CORNER_2,
+                HEAVY_CORNER_3,
+                HEAVY_CORNER_4,
+                HEAVY_INTERSECTION_UP,
+                HEAVY_INTERSECTION_DOWN,
+                HEAVY_INTERSECTION_LEFT,
+                HEAVY_INTERSECTION_RIGHT,
+                HEAVY_INTERSECTION_CROSS,
+            ],
+            LineWeight::Double => [
+                DOUBLE_VERTICAL_BAR,
+                DOUBLE_HORIZONTAL_BAR,
+                DOUBLE_CORNER_1,
+                DOUBLE_CORNER_2,
+                DOUBLE_CORNER_3,
+                DOUBLE_CORNER_4,
+                DOUBLE_INTERSECTION_UP,
+                DOUBLE_INTERSECTION_DOWN,
+                DOUBLE_INTERSECTION_LEFT,
+                DOUBLE_INTERSECTION_RIGHT,
+                DOUBLE_INTERSECTION_CROSS,
+            ],
+            LineWeight::Ascii => {
+                let mut glyphs = [ASCII_CORNER; 11];
+                glyphs[0] = ASCII_VERTICAL_BAR;
+                glyphs[1] = ASCII_HORIZONTAL_BAR;
+                glyphs
+            }
+        };
+    let (vertical, horizontal) = match weight {
+        LineWeight::Dashed => (DASHED_VERTICAL_BAR, DASHED_HORIZONTAL_BAR),
+        _ => (vertical, horizontal),
+    };
+    [
+        ' ', vertical, vertical, vertical, horizontal, corner_1, corner_4, left, horizontal,
+        corner_2, corner_3, right, horizontal, up, down, cross,
+    ]
+}
+
+/// Which directions the lines of a box drawing character go in
+fn get_directions(c: char) -> Option<(u8, LineWeight)> {
+    let c = match c {
+        CORNER_1_ROUNDED => CORNER_1,
+        CORNER_2_ROUNDED => CORNER_2,
+        CORNER_3_ROUNDED => CORNER_3,
+        CORNER_4_ROUNDED => CORNER_4,
+        c => c,
+    };
+    WEIGHTS.into_iter().find_map(|weight| {
+        let glyphs = get_glyphs(&weight);
+        // Only the flags for lines going both ways, since single directions share characters
+        [UP | DOWN, LEFT | RIGHT, 5, 6, 7, 9, 10, 11, 13, 14, 15]
+            .into_iter()
+            .find(|d| glyphs[*d as usize] == c)
+            .map(|d| (d, weight))
+    })
+}
+
+/// The character joining every line that passes through a cell. A line that hops goes straight
+/// over a line crossing it, leaving a gap in the other line
+fn get_junction(connections: &[Connection]) -> char {
+    let straight = |c: &Connection| c.directions == UP | DOWN || c.directions == LEFT | RIGHT;
+    if let [a, b] = connections {
+        if straight(a) && straight(b) && a.directions != b.directions {
+            if let Some(hop) = [b, a].into_iter().find(|c| c.hop) {
+                return get_glyphs(&hop.weight)[hop.directions as usize];
+            }
+        }
+    }
+    let directions = connections.iter().fold(0, |d, c| d | c.directions);
+    let weight = connections
+        .iter()
+        .map(|c| &c.weight)
+        .max_by_key(|w| match w {
+            LineWeight::Dashed => 0,
+            LineWeight::Light => 1,
+            LineWeight::Heavy => 2,
+            LineWeight::Double => 3,
+            LineWeight::Ascii => 4,
+        })
+        .unwrap_or(&LineWeight::Light);
+    get_glyphs(weight)[directions as usize]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        characters::*,
+        components::{
+            arrow::Arrow,
+            arrow_style::{HeadPosition, LineWeight},
+            rectangle::Rectangle,
+        },
+        draw::Draw,
+        shape::Shape,
+        state::State,
+        util::Vec2,
+    };
+
+    use super::Intersections;
+
+    fn arrow(points: Vec<(i32, i32)>) -> Arrow {
+        let mut arrow = Arrow::init();
+        arrow.points = points.into_iter().map(Vec2::from).collect();
+        arrow.style.head = HeadPosition::None;
+        arrow
+    }
+
+    fn junction_at(state: &State, position: (i32, i32)) -> Option<char> {
+        Intersections::new(state)
+            .draw()
+            .unwrap()
+            .into_iter()
+            .find(|p| p.origin == position.into())
+            .map(|p| p.character)
+    }
+
+    #[test]
+    fn should_join_arrow_endpoint_to_box_border() {
+        let mut state = State::init();
+        let mut rect = Rectangle::new_at(4, 0);
+        rect.width = 3;
+        rect.height = 3;
+        state.document.shapes.push(Shape::Rectangle(rect));
+        state
+            .document
+            .shapes
+            .push(Shape::Arrow(arrow(vec![(0, 1), (4, 1)])));
+        assert_eq!(junction_at(&state, (4, 1)), Some(INTERSECTION_LEFT));
+    }
+
+    #[test]
+    fn should_join_crossing_and_merging_arrows() {
+        let mut state = State::init();
+        let mut heavy = arrow(vec![(0, 2), (6, 2)]);
+        heavy.style.line = LineWeight::Heavy;
+        state.document.shapes.push(Shape::Arrow(heavy));
+        state
+            .document
+            .shapes
+            .push(Shape::Arrow(arrow(vec![(2, 0), (2, 4)])));
+        state
+            .document
+            .shapes
+            .push(Shape::Arrow(arrow(vec![(4, 0), (4, 2)])));
+        assert_eq!(junction_at(&state, (2, 2)), Some(HEAVY_INTERSECTION_CROSS));
+        assert_eq!(junction_at(&state, (4, 2)), Some(HEAVY_INTERSECTION_UP));
+    }
+
+    #[test]
+    fn should_hop_over_crossing_line() {
+        let mut state = State::init();
+        let mut hop = arrow(vec![(0, 2), (6, 2)]);
+        hop.style.hop = true;
+        state.document.shapes.push(Shape::Arrow(hop));
+        state
+            .document
+            .shapes
+            .push(Shape::Arrow(arrow(vec![(2, 0), (2, 4)])));
+        assert_eq!(junction_at(&state, (2, 2)), Some(HORIZONTAL_BAR));
+    }
+}
@@ -1,29 +1,43 @@
 use serde::{Deserialize, Serialize};
 use std::{
     cmp::Ordering::{Greater, Less},
-    ops::{Deref, DerefMut},
+    collections::HashMap,
+    ops::{Deref, DerefMut, Range},
 };
 
 use crate::{
     characters::{
         CORNER_1_ROUNDED, CORNER_2_ROUNDED, CORNER_3_ROUNDED, CORNER_4_ROUNDED, HORIZONTAL_BAR,
-        VERTICAL_BAR,
+        OVERFLOW, VERTICAL_BAR,
     },
-    cursor::{cursor_position, set_position},
+    components::text_style::{Align, AutoSize, TextStyle, VerticalAlign},
     cursor_guide::GuidePoint,
     draw::{Color, CursorIntersect, Draw, EdgeIntersection::Corner, Intersection, Point},
     mode::Anchor,
+    shape::Translate,
     shape_id::generate_shape_id,
-    util::Vec2,
+    util::{
+        text::{columns, deserialize_chars, display_width, graphemes, serialize_chars, EditText},
+        Vec2,
+    },
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Widest that text in a box gets when it is sized to fit
+const MAX_TEXT_WIDTH: i32 = 40;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Rectangle {
     pub origin: Vec2<i32>,
     pub width: i32,
     pub height: i32,
+    #[serde(
+        serialize_with = "serialize_chars",
+        deserialize_with = "deserialize_chars"
+    )]
     pub text: Vec<char>,
     pub shape_id: u32,
+    #[serde(default)]
+    pub text_style: TextStyle,
 }
 
 impl Deref for Rectangle {
@@ -47,34 +61,264 @@ impl Rectangle {
             height: 1,
             text: vec![],
             shape_id: generate_shape_id(),
+            text_style: TextStyle::default(),
         }
     }
 
-    pub fn on_char(&mut self, key: char) -> std::io::Result<()> {
-        self.text.push(key);
-        let next = self.get_inner_cursor_position();
-        set_position(next.into());
-        Ok(())
+    /// Which side of the border a point is on, and how far along that side it is. Corners are
+    /// not part of any side
+    pub fn border_at(&self, point: &Vec2<i32>) -> Option<(Border, i32)> {
+        let right = self.x + self.width - 1;
+        let bottom = self.y + self.height - 1;
+        let in_x = point.x > self.x && point.x < right;
+        let in_y = point.y > self.y && point.y < bottom;
+        if in_x && point.y == self.y {
+            Some((Border::Top, point.x - self.x))
+        } else if in_x && point.y == bottom {
+            Some((Border::Bottom, point.x - self.x))
+        } else if in_y && point.x == self.x {
+            Some((Border::Left, point.y - self.y))
+        } else if in_y && point.x == right {
+            Some((Border::Right, point.y - self.y))
+        } else {
+            None
+        }
     }
 
-    pub fn on_backspace(&mut self) -> std::io::Result<()> {
-        self.text.pop();
-        let next = self.get_inner_cursor_position();
-        set_position(next.into());
-        Ok(())
+    /// Position on a side of the border, kept away from the corners if the rectangle has shrunk
+    pub fn border_position(&self, border: &Border, offset: i32) -> Vec2<i32> {
+        let length = match border {
+            Border::Top | Border::Bottom => self.width,
+            Border::Left | Border::Right => self.height,
+        };
+        let offset = offset.min(length - 2).max(1);
+        match border {
+            Border::Top => Vec2::new(self.x + offset, self.y),
+            Border::Bottom => Vec2::new(self.x + offset, self.y + self.height - 1),
+            Border::Left => Vec2::new(self.x, self.y + offset),
+            Border::Right => Vec2::new(self.x + self.width - 1, self.y + offset),
+        }
     }
 
-    pub fn get_inner_cursor_position(&self) -> Vec2<i32> {
-        if self.width < 3 || self.height < 3 {
+    /// Where the cursor goes when it is before the character at `index` in the text
+    pub fn get_text_cursor_position(&self, index: usize) -> Vec2<i32> {
+        let (origin, width, _) = self.text_area();
+        let lines = self.layout();
+        let Some(line) = line_of(&lines, index).map(|row| &lines[row]) else {
             return Vec2::new(self.x, self.y);
+        };
+        let col = index - line.range.start;
+        // A full line puts the cursor at the start of the next one
+        let full = display_width(&self.text[line.range.clone()]) as i32 >= width;
+        if full && col == line.range.len() {
+            let x = match self.text_style.align {
+                Align::Center => width / 2,
+                Align::Right => width,
+                Align::Left | Align::Justify => 0,
+            };
+            return Vec2::new(origin.x + x, line.y + 1);
+        }
+        Vec2::new(line.columns[col], line.y)
+    }
+
+    /// Index in the text that is closest to being directly above or below `index`, `rows` lines
+    /// away
+    pub fn move_vertically(&self, index: usize, rows: i32) -> usize {
+        let lines = self.layout();
+        let Some(row) = line_of(&lines, index) else {
+            return index;
+        };
+        let target = (row as i32 + rows).clamp(0, lines.len() as i32 - 1) as usize;
+        if target == row {
+            return index;
+        }
+        let x = lines[row].columns[index - lines[row].range.start];
+        let line = &lines[target];
+        let col = (0..line.columns.len())
+            .min_by_key(|i| (line.columns[*i] - x).abs())
+            .unwrap_or(0);
+        line.range.start + col
+    }
+
+    /// Indices of the text on the same line as `index`
+    pub fn line_range(&self, index: usize) -> Range<usize> {
+        let lines = self.layout();
+        line_of(&lines, index).map_or(index..index, |row| lines[row].range.clone())
+    }
+
+    /// Resize to fit the text, using the same wrapping as when it is drawn. Boxes get wider, up to
+    /// a limit, before they get taller. If `shrink` is not set the box only grows. While `typing`
+    /// there is always room left for the cursor after the text
+    pub fn fit_text(&mut self, shrink: bool, typing: bool) {
+        let inset = 1 + self.text_style.padding;
+        let (_, width, height) = self.text_area();
+        let cursor = i32::from(typing);
+        let longest = self
+            .text
+            .split(|c| *c == '\n')
+            .map(|line| display_width(line) as i32 + cursor)
+            .max()
+            .unwrap_or(0);
+        let mut text_width = longest.clamp(1, MAX_TEXT_WIDTH);
+        if !shrink {
+            text_width = text_width.max(width);
+        }
+        let lines = wrap(&self.text, text_width as usize);
+        let mut text_height = lines.len() as i32;
+        let line_width = |line: &Range<usize>| display_width(&self.text[line.clone()]) as i32;
+        if lines.last().is_some_and(|l| line_width(l) >= text_width) {
+            text_height += cursor;
+        }
+        if shrink {
+            let widest = lines.iter().map(|l| line_width(l) + cursor).max();
+            text_width = widest.unwrap_or(0).clamp(1, text_width);
+        } else {
+            text_height = text_height.max(height);
+        }
+        self.width = text_width + inset * 2;
+        self.height = text_height.max(1) + inset * 2;
+    }
+
+    /// Fit the text if the box is set to change size automatically
+    pub fn auto_size(&mut self) {
+        match self.text_style.auto_size {
+            AutoSize::Off => {}
+            AutoSize::Grow => self.fit_text(false, true),
+            AutoSize::Fit => self.fit_text(true, true),
+        }
+    }
+
+    /// Top left corner, width and height of the space inside of the border and padding
+    fn text_area(&self) -> (Vec2<i32>, i32, i32) {
+        let inset = 1 + self.text_style.padding;
+        (
+            Vec2::new(self.x + inset, self.y + inset),
+            self.width - inset * 2,
+            self.height - inset * 2,
+        )
+    }
+
+    /// Wrap the text and work out where every character goes for the text style
+    fn layout(&self) -> Vec<TextLine> {
+        let (origin, width, height) = self.text_area();
+        if width <= 0 || height <= 0 {
+            return vec![];
+        }
+        let lines = wrap(&self.text, width as usize);
+        let visible = (lines.len() as i32).min(height);
+        let top = origin.y
+            + match self.text_style.vertical_align {
+                VerticalAlign::Top => 0,
+                VerticalAlign::Middle => (height - visible) / 2,
+                VerticalAlign::Bottom => height - visible,
+            };
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(row, range)| {
+                let text = &self.text[range.clone()];
+                let offsets = columns(text);
+                let free = width - offsets[text.len()] as i32;
+                // The last line of a paragraph is not stretched
+                let paragraph_end = self.text.get(range.end).is_none_or(|c| *c == '\n');
+                let (start, gaps) = match self.text_style.align {
+                    Align::Left => (0, 0),
+                    Align::Center => (free / 2, 0),
+                    Align::Right => (free, 0),
+                    Align::Justify if paragraph_end => (0, 0),
+                    Align::Justify => (0, free),
+                };
+                let spaces = text.iter().filter(|c| **c == ' ').count() as i32;
+                let mut extra = 0;
+                let mut seen = 0;
+                let mut columns = vec![];
+                for (i, offset) in offsets.into_iter().enumerate() {
+                    columns.push(origin.x + start + offset as i32 + extra);
+                    if text.get(i) == Some(&' ') {
+                        // Spread the extra space evenly, with the first gaps taking any remainder
+                        extra += gaps / spaces + i32::from(seen < gaps % spaces);
+                        seen += 1;
+                    }
+                }
+                TextLine {
+                    range,
+                    y: top + row as i32,
+                    columns,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Which line an index in the text is on. An index at the boundary of two lines that were split
+/// mid word is at the start of the second one
+fn line_of(lines: &[TextLine], index: usize) -> Option<usize> {
+    lines
+        .iter()
+        .position(|l| l.range.contains(&index))
+        .or_else(|| lines.iter().position(|l| l.range.end == index))
+}
+
+/// Where a line of text is drawn. `columns` has the x position of each of its characters, then
+/// the position just after the last one. Wide characters take up two columns, and characters that
+/// combine with the one before them share its column
+struct TextLine {
+    range: Range<usize>,
+    y: i32,
+    columns: Vec<i32>,
+}
+
+/// Split text into the lines it is drawn on, as ranges of indices into the text. Lines end at
+/// newlines, or wrap at the last space that fits. Words that are longer than a whole line are
+/// split wherever the line runs out, but never in the middle of a grapheme. Width is counted in
+/// cells, so wide characters take up two. The newline or space a line wraps at is not part of
+/// either line.
+pub fn wrap(text: &[char], width: usize) -> Vec<Range<usize>> {
+    let mut lines = vec![];
+    if width == 0 {
+        return lines;
+    }
+    let mut start = 0;
+    // Cells taken up by the current line so far
+    let mut used = 0;
+    // Last space on the current line that it could wrap at
+    let mut space = None;
+    for (range, cells) in graphemes(text) {
+        let i = range.start;
+        if text[i] == '\n' {
+            lines.push(start..i);
+            (start, used, space) = (range.end, 0, None);
+            continue;
+        }
+        if used + cells > width && used > 0 {
+            if text[i] == ' ' {
+                lines.push(start..i);
+                (start, used, space) = (range.end, 0, None);
+                continue;
+            }
+            match space.take() {
+                Some(space) => {
+                    lines.push(start..space);
+                    start = space + 1;
+                    used = display_width(&text[start..i]);
+                }
+                None => {
+                    lines.push(start..i);
+                    (start, used) = (i, 0);
+                }
+            }
+            if used + cells > width && used > 0 {
+                lines.push(start..i);
+                (start, used) = (i, 0);
+            }
         }
-        let text_width = self.width - 2;
-        let col = self.text.len() as i32 % text_width;
-        let row = self.text.len() as i32 / text_width;
-        let cursor_x = self.x + 1 + col;
-        let cursor_y = self.y + 1 + row;
-        Vec2::new(cursor_x, cursor_y)
+        if text[i] == ' ' && i > start {
+            space = Some(i);
+        }
+        used += cells;
     }
+    lines.push(start..text.len());
+    lines
 }
 
 impl Draw for Rectangle {
@@ -82,6 +326,27 @@ impl Draw for Rectangle {
         let mut points = vec![];
         let foreground = Color::Border;
         let background = Color::BorderBackground;
+        let (text_origin, text_width, text_height) = self.text_area();
+        let lines = self.layout();
+        // Every grapheme, all of its characters drawn in the same cell
+        let mut text: HashMap<Vec2<i32>, Vec<char>> = HashMap::new();
+        for line in lines.iter().filter(|l| l.y < text_origin.y + text_height) {
+            for (i, c) in self.text[line.range.clone()].iter().enumerate() {
+                let position = Vec2::new(line.columns[i], line.y);
+                text.entry(position).or_default().push(*c);
+            }
+        }
+        // Show that there is more text than fits in the box
+        if lines.len() as i32 > text_height {
+            let corner = text_origin + Vec2::new(text_width - 1, text_height - 1);
+            text.insert(corner, vec![OVERFLOW]);
+        }
+        // The right halves of wide characters, which are left for the renderer to fill
+        let covered: Vec<Vec2<i32>> = text
+            .iter()
+            .filter(|(_, grapheme)| display_width(grapheme) > 1)
+            .map(|(position, _)| position.clone() + Vec2::new(1, 0))
+            .collect();
 
         for y in 0..self.height {
             for x in 0..self.width {
@@ -90,13 +355,13 @@ impl Draw for Rectangle {
                 let is_first_col = x == 0;
                 let is_last_col = x == self.width - 1;
 
-                let char_index = x - 1 + (y - 1) * (self.width - 2);
-
-                let mut to_draw = ' ';
-
-                if char_index >= 0 && (char_index as usize) < self.text.len() {
-                    to_draw = self.text[char_index as usize];
+                let position = Vec2::new(self.x + x, self.y + y);
+                let is_border = is_first_row || is_last_row || is_first_col || is_last_col;
+                if !is_border && covered.contains(&position) {
+                    continue;
                 }
+                let grapheme = text.get(&position).map(Vec::as_slice).unwrap_or(&[' ']);
+                let mut to_draw = grapheme[0];
 
                 if is_first_row && is_first_col {
                     to_draw = CORNER_3_ROUNDED;
@@ -113,14 +378,19 @@ impl Draw for Rectangle {
                 }
 
                 points.push(Point {
-                    origin: Vec2 {
-                        x: self.x + x,
-                        y: self.y + y,
-                    },
+                    origin: position.clone(),
                     character: to_draw,
                     foreground,
                     background,
                 });
+                if !is_border {
+                    points.extend(grapheme[1..].iter().map(|c| Point {
+                        origin: position.clone(),
+                        character: *c,
+                        foreground,
+                        background,
+                    }));
+                }
             }
         }
 
@@ -128,6 +398,56 @@ impl Draw for Rectangle {
     }
 }
 
+/// One of the four sides of a rectangle's border
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Border {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+impl Border {
+    /// Direction pointing out of the rectangle
+    pub fn normal(&self) -> Vec2<i32> {
+        match self {
+            Border::Top => (0, -1),
+            Border::Right => (1, 0),
+            Border::Bottom => (0, 1),
+            Border::Left => (-1, 0),
+        }
+        .into()
+    }
+}
+
+impl EditText for Rectangle {
+    fn text(&self) -> &Vec<char> {
+        &self.text
+    }
+
+    fn text_mut(&mut self) -> &mut Vec<char> {
+        &mut self.text
+    }
+
+    fn is_multiline(&self) -> bool {
+        true
+    }
+
+    fn line_range(&self, index: usize) -> Range<usize> {
+        Rectangle::line_range(self, index)
+    }
+
+    fn move_vertically(&self, index: usize, rows: i32) -> usize {
+        Rectangle::move_vertically(self, index, rows)
+    }
+}
+
+impl Translate for Rectangle {
+    fn translate(&mut self, delta: &Vec2<i32>) {
+        self.origin = self.origin.clone() + delta.clone();
+    }
+}
+
 impl CursorIntersect for Rectangle {
     fn get_intersection(&self, Vec2 { x, y }: &Vec2<i32>) -> Intersection {
         let c_x = *x;
@@ -185,10 +505,9 @@ impl Drag for Rectangle {
 pub trait Drag {
     fn rect(&mut self) -> (&mut i32, &mut i32, &mut i32, &mut i32);
 
-    fn drag_corner(&mut self, anchor: &mut Anchor) -> std::io::Result<()> {
-        let position = cursor_position();
-        let cursor_x = position.x as i32;
-        let cursor_y = position.y as i32;
+    fn drag_corner(&mut self, anchor: &mut Anchor, cursor: &Vec2<i32>) -> std::io::Result<()> {
+        let cursor_x = cursor.x;
+        let cursor_y = cursor.y;
         self.adjust_anchor(anchor, &cursor_x, &cursor_y);
         match anchor {
             Anchor::TopLeft => {
@@ -295,7 +614,136 @@ impl GuidePoint for Rectangle {
 mod test {
     use crate::util::Vec2;
 
-    use super::Rectangle;
+    use crate::{
+        components::text_style::{Align, TextStyle, VerticalAlign},
+        draw::Draw,
+    };
+
+    use super::{wrap, Border, Rectangle};
+
+    /// The text inside of a rectangle as it is drawn, one string per row
+    fn rows(rect: &Rectangle) -> Vec<String> {
+        let points = rect.draw().unwrap();
+        (1..rect.height - 1)
+            .map(|y| {
+                (1..rect.width - 1)
+                    .map(|x| {
+                        let position = Vec2::new(rect.x + x, rect.y + y);
+                        points
+                            .iter()
+                            .find(|p| p.origin == position)
+                            .unwrap()
+                            .character
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn text_rect(text: &str, width: i32, height: i32) -> Rectangle {
+        let mut rect = Rectangle::new_at(0, 0);
+        rect.width = width;
+        rect.height = height;
+        rect.text = text.chars().collect();
+        rect
+    }
+
+    #[test]
+    fn should_align_text() {
+        let mut rect = text_rect("ab", 6, 5);
+        rect.text_style.align = Align::Right;
+        rect.text_style.vertical_align = VerticalAlign::Bottom;
+        assert_eq!(rows(&rect), vec!["    ", "    ", "  ab"]);
+        assert_eq!(
+            rect.get_text_cursor_position(rect.text.len()),
+            Vec2::new(5, 3)
+        );
+
+        rect.text_style.align = Align::Center;
+        rect.text_style.vertical_align = VerticalAlign::Middle;
+        assert_eq!(rows(&rect), vec!["    ", " ab ", "    "]);
+    }
+
+    #[test]
+    fn should_justify_all_but_last_line() {
+        let mut rect = text_rect("a b c dd e", 9, 4);
+        rect.text_style.align = Align::Justify;
+        assert_eq!(rows(&rect), vec!["a  b  c", "dd e   "]);
+    }
+
+    #[test]
+    fn should_move_cursor_between_lines() {
+        let rect = text_rect("one two three", 7, 5);
+        assert_eq!(rect.move_vertically(1, 1), 5);
+        assert_eq!(rect.move_vertically(6, 1), 10);
+        assert_eq!(rect.move_vertically(10, -2), 2);
+        assert_eq!(rect.line_range(5), 4..7);
+    }
+
+    #[test]
+    fn should_fit_box_to_text() {
+        let mut rect = text_rect("hello\nhi", 20, 10);
+        rect.fit_text(true, false);
+        assert_eq!((rect.width, rect.height), (7, 4));
+
+        let mut rect = text_rect(&"word ".repeat(20), 5, 3);
+        rect.fit_text(false, true);
+        assert_eq!((rect.width, rect.height), (42, 5));
+        assert_eq!(rows(&rect).len(), 3);
+    }
+
+    #[test]
+    fn should_keep_text_inside_padding() {
+        let mut rect = text_rect("abcdef", 6, 6);
+        rect.text_style.padding = 1;
+        assert_eq!(rows(&rect), vec!["    ", " ab ", " c… ", "    "]);
+    }
+
+    fn lines(text: &str, width: usize) -> Vec<String> {
+        let text: Vec<char> = text.chars().collect();
+        wrap(&text, width)
+            .into_iter()
+            .map(|line| text[line].iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn should_wrap_at_words() {
+        assert_eq!(lines("the quick fox", 6), vec!["the", "quick", "fox"]);
+        assert_eq!(lines("abcdefg hi", 3), vec!["abc", "def", "g", "hi"]);
+    }
+
+    #[test]
+    fn should_wrap_wide_characters_by_cells() {
+        assert_eq!(lines("日本語 ab", 4), vec!["日本", "語", "ab"]);
+        let accented = "e\u{301}";
+        assert_eq!(
+            lines(&accented.repeat(3), 2),
+            vec![accented.repeat(2), accented.to_string()]
+        );
+
+        let rect = text_rect("日本", 6, 4);
+        assert_eq!(rect.get_text_cursor_position(1), Vec2::new(3, 1));
+        assert_eq!(rect.get_text_cursor_position(2), Vec2::new(1, 2));
+    }
+
+    #[test]
+    fn should_start_new_line_after_newline() {
+        assert_eq!(lines("ab\n\ncd", 5), vec!["ab", "", "cd"]);
+        assert_eq!(lines("ab\n", 5), vec!["ab", ""]);
+    }
+
+    #[test]
+    fn should_put_cursor_after_wrapped_word() {
+        let mut rect = Rectangle::new_at(0, 0);
+        rect.width = 7;
+        rect.height = 5;
+        rect.text = "the qui".chars().collect();
+        assert_eq!(
+            rect.get_text_cursor_position(rect.text.len()),
+            Vec2::new(4, 2)
+        );
+    }
 
     #[test]
     fn should_get_cursor_position_when_editing_text() {
@@ -305,9 +753,29 @@ mod test {
             height: 4,
             text: vec!['0', '1', '2'],
             shape_id: 1,
+            text_style: TextStyle::default(),
         };
-        let pos = rect.get_inner_cursor_position();
+        let pos = rect.get_text_cursor_position(rect.text.len());
         let expected = (7, 7);
         assert_eq!(pos, expected.into());
     }
+
+    #[test]
+    fn should_get_border_side_and_offset() {
+        let mut rect = Rectangle::new_at(2, 2);
+        rect.width = 5;
+        rect.height = 4;
+        assert_eq!(rect.border_at(&(4, 2).into()), Some((Border::Top, 2)));
+        assert_eq!(rect.border_at(&(6, 3).into()), Some((Border::Right, 1)));
+        assert_eq!(rect.border_at(&(2, 2).into()), None);
+        assert_eq!(rect.border_at(&(4, 3).into()), None);
+    }
+
+    #[test]
+    fn should_keep_border_position_away_from_corners() {
+        let mut rect = Rectangle::new_at(0, 0);
+        rect.width = 4;
+        rect.height = 4;
+        assert_eq!(rect.border_position(&Border::Bottom, 5), Vec2::new(2, 3));
+    }
 }
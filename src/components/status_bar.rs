@@ -1,41 +1,94 @@
 use crossterm::terminal;
 
 use crate::{
-    cursor::cursor_position,
-    draw::{DrawSticky, Point},
+    draw::{Color, DrawSticky, Point},
     mode::Mode,
+    state::State,
     util::Vec2,
 };
 
 #[derive(Default)]
 pub struct StatusBar {
-    mode_text: &'static str,
+    mode_text: String,
+    /// Shown after the mode, in `message_color`
+    message_text: String,
+    message_color: Option<Color>,
     cursor_text: String,
     y: u16,
 }
 
+/// Shown in the status bar until the next key is pressed
+pub struct Message {
+    pub text: String,
+    pub is_error: bool,
+}
+
+impl Message {
+    pub fn info(text: String) -> Self {
+        Self {
+            text,
+            is_error: false,
+        }
+    }
+
+    pub fn error(text: String) -> Self {
+        Self {
+            text,
+            is_error: true,
+        }
+    }
+}
+
+/// Added to the mode when there are changes that have not been saved
+const MODIFIED: &str = " [+]";
+
 const NORMAL: &str = "Normal";
 const DRAW: &str = "Draw";
 const TEXT: &str = "Text";
+const TEXT_NORMAL: &str = "Text Normal";
 const ARROW: &str = "Arrow";
 const SELECT: &str = "Select";
+const MOVE: &str = "Move";
+const ROUTE: &str = "Route";
+const EDIT: &str = "Edit";
+const LABEL: &str = "Label";
 
 impl StatusBar {
-    pub fn new(mode: &Mode, y_offset: u16) -> Self {
-        let mode_text = match mode {
-            Mode::Normal => NORMAL,
-            Mode::DrawRectangle(_, _) => DRAW,
-            Mode::DrawArrow(_) => ARROW,
-            Mode::Text(_) => TEXT,
-            Mode::Select(_) => SELECT,
+    pub fn new(state: &State, cursor: &Vec2<i32>, y_offset: u16) -> Self {
+        let mut mode_text = match &state.mode {
+            Mode::Normal => NORMAL.to_string(),
+            Mode::DrawRectangle(_, _) => DRAW.to_string(),
+            Mode::DrawArrow(_) => ARROW.to_string(),
+            Mode::Text(edit) if edit.normal => TEXT_NORMAL.to_string(),
+            Mode::Text(_) => TEXT.to_string(),
+            Mode::Select(_) => SELECT.to_string(),
+            Mode::Move(_) => MOVE.to_string(),
+            Mode::Route(_) => ROUTE.to_string(),
+            Mode::EditArrow(_) => EDIT.to_string(),
+            Mode::Label(_) => LABEL.to_string(),
+            Mode::Command(command) => format!(":{command}"),
+        };
+        if state.is_dirty() {
+            mode_text.push_str(MODIFIED);
+        }
+        let (message_text, message_color) = match &state.message {
+            Some(message) => (
+                format!("  {}", message.text),
+                Some(match message.is_error {
+                    true => Color::Error,
+                    false => Color::Empty,
+                }),
+            ),
+            None => (String::new(), None),
         };
 
-        let position = cursor_position();
-        let cursor_text = format!("{}:{}", position.x, position.y);
+        let cursor_text = format!("{}:{}", cursor.x, cursor.y);
         let y = y_offset + 1;
 
         Self {
             mode_text,
+            message_text,
+            message_color,
             cursor_text,
             y,
         }
@@ -48,12 +101,20 @@ impl DrawSticky for StatusBar {
         let mut row = vec![];
 
         let cursor_text_length = self.cursor_text.chars().count();
+        let mode_text_length = self.mode_text.chars().count();
 
         for x in 0..w {
             let mut next_char = ' ';
+            let mut foreground = Color::Empty;
 
             if let Some(c) = self.mode_text.chars().nth(x as usize) {
                 next_char = c;
+            } else if let Some(c) = (x as usize)
+                .checked_sub(mode_text_length)
+                .and_then(|i| self.message_text.chars().nth(i))
+            {
+                next_char = c;
+                foreground = self.message_color.unwrap_or(Color::Empty);
             }
 
             let distance_from_end = (w as usize).abs_diff(x as usize);
@@ -65,14 +126,15 @@ impl DrawSticky for StatusBar {
                     .nth(distance_from_end.abs_diff(cursor_text_length))
                 {
                     next_char = c;
+                    foreground = Color::Empty;
                 }
             }
 
             row.push(Point {
                 origin: Vec2 { x, y: h - self.y },
                 character: next_char,
-                foreground: crate::draw::Color::Empty,
-                background: crate::draw::Color::EmptyBackground,
+                foreground,
+                background: Color::EmptyBackground,
             });
         }
 
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+/// How the text inside of a box is laid out
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TextStyle {
+    #[serde(default)]
+    pub align: Align,
+    #[serde(default)]
+    pub vertical_align: VerticalAlign,
+    /// Empty cells between the border and the text, on every side
+    #[serde(default)]
+    pub padding: i32,
+    #[serde(default)]
+    pub auto_size: AutoSize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+    /// Spread the words out to fill the whole line, except for the last line of a paragraph
+    Justify,
+}
+
+impl Align {
+    pub fn next(&self) -> Self {
+        match self {
+            Align::Left => Align::Center,
+            Align::Center => Align::Right,
+            Align::Right => Align::Justify,
+            Align::Justify => Align::Left,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum VerticalAlign {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl VerticalAlign {
+    pub fn next(&self) -> Self {
+        match self {
+            VerticalAlign::Top => VerticalAlign::Middle,
+            VerticalAlign::Middle => VerticalAlign::Bottom,
+            VerticalAlign::Bottom => VerticalAlign::Top,
+        }
+    }
+}
+
+/// Whether a box changes size to fit its text while it is typed
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum AutoSize {
+    #[default]
+    Off,
+    /// Get bigger when the text does not fit, but never smaller
+    Grow,
+    /// Always be the size of the text
+    Fit,
+}
+
+impl AutoSize {
+    pub fn next(&self) -> Self {
+        match self {
+            AutoSize::Off => AutoSize::Grow,
+            AutoSize::Grow => AutoSize::Fit,
+            AutoSize::Fit => AutoSize::Off,
+        }
+    }
+}
@@ -1,8 +1,6 @@
-use std::{
-    cmp::Ordering,
-    io::stdout,
-    sync::{OnceLock, RwLock},
-};
+use std::io::stdout;
+#[cfg(not(test))]
+use std::sync::{OnceLock, RwLock};
 
 use crossterm::{cursor, queue};
 
@@ -15,67 +13,58 @@ use crate::util::Vec2;
 ///
 /// So instead we track the cursor position locally and try to keep it in sync with where the
 /// cursor is being moved in stdout
-
+#[cfg(not(test))]
 static CURSOR: OnceLock<RwLock<Cursor>> = OnceLock::new();
 
+// Tests have no terminal to ask where the cursor starts, and each test gets a cursor of its own so
+// that tests running at the same time do not move each other's cursor
+#[cfg(test)]
+thread_local! {
+    static CURSOR: std::cell::RefCell<Cursor> = std::cell::RefCell::new(Cursor {
+        position: Vec2::new(0, 0),
+        saved_position: None,
+    });
+}
+
 struct Cursor {
     position: Vec2<u16>,
     saved_position: Option<Vec2<u16>>,
 }
 
-pub fn cursor_position() -> Vec2<u16> {
-    CURSOR.get_or_init(init).read().unwrap().position.clone()
+#[cfg(not(test))]
+fn with_cursor<T>(f: impl FnOnce(&mut Cursor) -> T) -> T {
+    f(&mut CURSOR.get_or_init(init).write().unwrap())
+}
+
+#[cfg(test)]
+fn with_cursor<T>(f: impl FnOnce(&mut Cursor) -> T) -> T {
+    CURSOR.with_borrow_mut(f)
 }
 
-pub fn adjust_position(Vec2 { x, y }: Vec2<i16>) {
-    let mut cursor = CURSOR.get().unwrap().write().unwrap();
-    match x.cmp(&0) {
-        Ordering::Less => {
-            let _ = queue!(stdout(), cursor::MoveLeft(x.unsigned_abs()));
-            cursor.position.x -= x.unsigned_abs();
-        }
-        Ordering::Greater => {
-            let _ = queue!(stdout(), cursor::MoveRight(x as u16));
-            cursor.position.x += x as u16;
-        }
-        _ => {}
-    }
-    match y.cmp(&0) {
-        Ordering::Less => {
-            let _ = queue!(stdout(), cursor::MoveLeft(y.unsigned_abs()));
-            cursor.position.y -= y.unsigned_abs();
-        }
-        Ordering::Greater => {
-            let _ = queue!(stdout(), cursor::MoveRight(y as u16));
-            cursor.position.y += y as u16;
-        }
-        _ => {}
-    }
+pub fn cursor_position() -> Vec2<u16> {
+    with_cursor(|cursor| cursor.position.clone())
 }
 
 pub fn set_position(Vec2 { x, y }: Vec2<u16>) {
     let _ = queue!(stdout(), cursor::MoveTo(x, y));
-    let mut cursor = CURSOR.get_or_init(init).write().unwrap();
-    cursor.position.x = x;
-    cursor.position.y = y;
+    with_cursor(|cursor| {
+        cursor.position.x = x;
+        cursor.position.y = y;
+    });
 }
 
 pub fn save_position() {
     let _ = queue!(stdout(), cursor::SavePosition);
-    let mut cursor = CURSOR.get_or_init(init).write().unwrap();
-    cursor.saved_position = Some(cursor.position.clone());
+    with_cursor(|cursor| cursor.saved_position = Some(cursor.position.clone()));
 }
 
 pub fn restore_position() {
     let _ = queue!(stdout(), cursor::RestorePosition);
-    let position = {
-        let mut cursor = CURSOR.get_or_init(init).write().unwrap();
-        let saved = cursor.saved_position.take();
-        saved.unwrap()
-    };
-    set_position(position)
+    let saved = with_cursor(|cursor| cursor.saved_position.take());
+    set_position(saved.unwrap())
 }
 
+#[cfg(not(test))]
 fn init() -> RwLock<Cursor> {
     let position = cursor::position().expect("Failed to init cursor position");
     RwLock::new(Cursor {
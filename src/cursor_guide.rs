@@ -1,21 +1,22 @@
 use crate::{
     characters::{HORIZONTAL_BAR, VERTICAL_BAR},
-    cursor::cursor_position,
     draw::{Color, Draw, Point},
     util::Vec2,
 };
 
 pub struct CursorGuide {
     points: Vec<Vec2<i32>>,
+    cursor: Vec2<i32>,
 }
 
 impl CursorGuide {
-    pub fn new(shapes: &[impl GuidePoint]) -> Self {
+    pub fn new(shapes: &[impl GuidePoint], cursor: Vec2<i32>) -> Self {
         Self {
             points: shapes
                 .iter()
                 .flat_map(|s| s.get_intersection_points())
                 .collect(),
+            cursor,
         }
     }
 
@@ -31,7 +32,7 @@ impl CursorGuide {
 
 impl Draw for CursorGuide {
     fn draw(&self) -> std::io::Result<Vec<Point<i32>>> {
-        let c: Vec2<i32> = cursor_position().into();
+        let c = &self.cursor;
         let mut points = vec![];
         self.points
             .iter()
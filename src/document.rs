@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{shape::Shape, util::Vec2};
+
+/// A drawing, which is everything that is saved to a file. What the editor is doing with it, like
+/// the current mode and undo history, is kept in the state instead
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Document {
+    pub shapes: Vec<Shape>,
+    #[serde(default)]
+    pub metadata: Metadata,
+    #[serde(default)]
+    pub canvas: Canvas,
+}
+
+/// Information about a drawing that is not part of what is drawn
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Metadata {
+    /// Version of traw that last saved the drawing
+    #[serde(default)]
+    pub saved_with: Option<String>,
+}
+
+/// Settings for the canvas that the shapes are drawn on
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Canvas {
+    /// Canvas position shown in the top left of the screen when the drawing is opened
+    #[serde(default)]
+    pub offset: Vec2<i32>,
+}
@@ -1,8 +1,10 @@
 use std::{io::Result, ops::Deref};
 
-use crate::{cursor::cursor_position, mode::Anchor, util::Vec2};
+use crate::{mode::Anchor, util::Vec2};
 
-/// Used for rendering an object at a specific location on the canvas
+/// Used for rendering an object at a specific location on the canvas. Characters that combine
+/// into one grapheme with the character before them, like accents, are drawn at the same position
+/// as it, right after it
 pub trait Draw {
     fn draw(&self) -> std::io::Result<Vec<Point<i32>>>;
 }
@@ -20,15 +22,8 @@ pub trait DrawOverlay {
 /// Used for determining how the cursor aligns with an object
 pub trait CursorIntersect {
     fn get_intersection(&self, point: &Vec2<i32>) -> Intersection;
-    fn get_cursor_intersection(&self) -> std::io::Result<Intersection> {
-        let position = cursor_position();
-        Ok(self.get_intersection(&position.into()))
-    }
-    fn hovered(&self) -> std::io::Result<bool> {
-        Ok(!matches!(
-            self.get_cursor_intersection()?,
-            Intersection::None
-        ))
+    fn hovered(&self, cursor: &Vec2<i32>) -> bool {
+        !matches!(self.get_intersection(cursor), Intersection::None)
     }
 }
 
@@ -86,11 +81,12 @@ pub enum Color {
     DebugBackground,
     Grid,
     Guide,
+    Error,
 }
 
-impl From<Color> for crossterm::style::Color {
-    fn from(value: Color) -> Self {
-        let (r, g, b) = match value {
+impl Color {
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        match self {
             Color::Border => (255, 255, 255),
             Color::BorderBackground => (0, 0, 0),
             Color::BorderBackgroundHover => (70, 70, 70),
@@ -100,7 +96,14 @@ impl From<Color> for crossterm::style::Color {
             Color::EmptyBackground => (0, 0, 0),
             Color::Grid => (100, 100, 40),
             Color::Guide => (120, 20, 20),
-        };
+            Color::Error => (240, 80, 80),
+        }
+    }
+}
+
+impl From<Color> for crossterm::style::Color {
+    fn from(value: Color) -> Self {
+        let (r, g, b) = value.rgb();
         Self::Rgb { r, g, b }
     }
 }
@@ -0,0 +1,463 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{ErrorKind, Write},
+    path::Path,
+};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    components::{
+        arrow::Arrow,
+        arrow_style::{HeadGlyphs, HeadPosition, LineWeight},
+        intersections::Intersections,
+        rectangle::Rectangle,
+    },
+    document::Document,
+    draw::{Color, Draw, Point},
+    persistence::load,
+    renderer::Renderer,
+    shape::Shape,
+    state::State,
+    util::Vec2,
+};
+
+/// Formats a drawing can be exported to
+#[derive(Debug, PartialEq)]
+pub enum Format {
+    /// The characters that are drawn in the terminal, as plain text
+    Txt,
+    /// Boxes, lines and text laid out on the same grid as in the terminal, for web pages and slides
+    Svg,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match name {
+            "txt" => Some(Format::Txt),
+            "svg" => Some(Format::Svg),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Txt => "txt",
+            Format::Svg => "svg",
+        }
+    }
+}
+
+/// The drawing in an export format
+pub fn export(document: Document, format: &Format) -> std::io::Result<String> {
+    match format {
+        Format::Txt => to_text(document),
+        Format::Svg => to_svg(&document),
+    }
+}
+
+/// Where the export of a drawing goes when it is exported from the editor, next to the drawing
+/// with the extension of the format
+pub fn export_path(file_name: &str, format: &Format) -> String {
+    Path::new(file_name)
+        .with_extension(format.extension())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// `traw export file.traw [--format txt|svg]`, writes the drawing to stdout without touching the
+/// terminal, so that it can be used in scripts
+pub fn run(args: &[String]) -> std::io::Result<()> {
+    let mut file_name = None;
+    let mut format = Format::Txt;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let name = args.next().map_or("", String::as_str);
+                format = Format::parse(name).ok_or_else(|| {
+                    std::io::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Unknown format '{name}', expected txt or svg"),
+                    )
+                })?;
+            }
+            _ if file_name.is_none() => file_name = Some(arg.as_str()),
+            _ => {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Unexpected argument '{arg}'"),
+                ))
+            }
+        }
+    }
+    let Some(file_name) = file_name else {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            "Usage: traw export file.traw [--format txt|svg]",
+        ));
+    };
+
+    let output = export(load(file_name)?, &format)?;
+    std::io::stdout().write_all(output.as_bytes())
+}
+
+/// Every shape drawn the way it is in the terminal, with the box drawing junctions where lines
+/// meet, cut down to the part of the canvas that has something on it
+pub fn to_text(document: Document) -> std::io::Result<String> {
+    let state = State::open(document);
+    let mut layers = vec![];
+    for shape in &state.document.shapes {
+        layers.push((shape.draw()?, Some(shape.shape_id())));
+    }
+    layers.push((Intersections::new(&state).draw()?, None));
+
+    let points = layers.iter().flat_map(|(points, _)| points);
+    let Some((min, max)) = bounds(points) else {
+        return Ok(String::new());
+    };
+    // One more column than the bounds, for the right half of a wide character at the edge
+    let width = max.x - min.x + 2;
+    let height = max.y - min.y + 1;
+    let size = |n: i32| {
+        u16::try_from(n).map_err(|_| {
+            std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "Drawing is too large to export as text",
+            )
+        })
+    };
+    let mut renderer = Renderer::new(size(width)?, size(height)?);
+    renderer.render_offscreen(&min, |r| {
+        for (points, shape_id) in &layers {
+            r.render(points.clone(), *shape_id)?;
+        }
+        Ok(())
+    })?;
+
+    // Points can be drawn as spaces, which leave blank rows and columns around the edges
+    let lines = renderer.lines();
+    let first = lines.iter().position(|line| !line.is_empty()).unwrap_or(0);
+    let last = lines.iter().rposition(|line| !line.is_empty()).unwrap_or(0);
+    let lines = &lines[first..=last];
+    let indent = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    Ok(lines
+        .iter()
+        .map(|line| format!("{}\n", line.get(indent..).unwrap_or("")))
+        .collect())
+}
+
+/// Top left and bottom right corners of the smallest area that covers every point
+fn bounds<'a>(points: impl Iterator<Item = &'a Point<i32>>) -> Option<(Vec2<i32>, Vec2<i32>)> {
+    points.fold(None, |bounds, point| {
+        let (min, max) = bounds.unwrap_or((point.origin.clone(), point.origin.clone()));
+        Some((
+            Vec2::new(min.x.min(point.origin.x), min.y.min(point.origin.y)),
+            Vec2::new(max.x.max(point.origin.x), max.y.max(point.origin.y)),
+        ))
+    })
+}
+
+/// Size of a cell of the terminal grid in the SVG
+const CELL_WIDTH: f32 = 10.0;
+const CELL_HEIGHT: f32 = 20.0;
+const FONT_SIZE: f32 = 16.0;
+const STROKE_WIDTH: f32 = 1.5;
+
+/// Boxes become rounded rectangles and arrows become lines with markers for heads, each running
+/// through the middle of the cells they are drawn in, so that the picture matches the terminal
+pub fn to_svg(document: &Document) -> std::io::Result<String> {
+    let mut drawn = vec![];
+    for shape in &document.shapes {
+        drawn.push((shape, shape.draw()?));
+    }
+    let Some((min, max)) = bounds(drawn.iter().flat_map(|(_, points)| points)) else {
+        return Ok(svg_document(&Vec2::new(0, 0), String::new()));
+    };
+    // A cell of space around the drawing
+    let origin = min - Vec2::new(1, 1);
+    let size = max - origin.clone() + Vec2::new(2, 2);
+
+    let mut body = String::new();
+    for (shape, points) in &drawn {
+        match shape {
+            Shape::Rectangle(rect) => svg_rectangle(&mut body, rect, points, &origin),
+            Shape::Arrow(arrow) => svg_arrow(&mut body, arrow, points, &origin),
+        }
+    }
+    Ok(svg_document(&size, body))
+}
+
+fn svg_document(size: &Vec2<i32>, body: String) -> String {
+    let (width, height) = (size.x as f32 * CELL_WIDTH, size.y as f32 * CELL_HEIGHT);
+    let background = hex(Color::EmptyBackground);
+    let foreground = hex(Color::Border);
+    let fill = hex(Color::BorderBackground);
+    let markers: String = HEADS
+        .iter()
+        .map(|(glyphs, shape)| {
+            format!(
+                "  <marker id=\"{}\" viewBox=\"0 0 10 10\" refX=\"{}\" refY=\"5\" \
+                markerWidth=\"10\" markerHeight=\"10\" markerUnits=\"userSpaceOnUse\" \
+                orient=\"auto-start-reverse\">{shape}</marker>\n",
+                marker_id(glyphs),
+                match glyphs {
+                    HeadGlyphs::Diamond | HeadGlyphs::Circle => 5,
+                    _ => 10,
+                }
+            )
+        })
+        .collect();
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+        viewBox=\"0 0 {width} {height}\">\n\
+        <style>\n\
+        \x20 rect {{ fill: {fill}; stroke: {foreground}; stroke-width: {STROKE_WIDTH}; }}\n\
+        \x20 polyline {{ fill: none; stroke: {foreground}; stroke-width: {STROKE_WIDTH}; }}\n\
+        \x20 marker * {{ fill: {foreground}; stroke: {foreground}; }}\n\
+        \x20 text {{ fill: {foreground}; font-family: monospace; font-size: {FONT_SIZE}px; \
+        text-anchor: middle; dominant-baseline: central; }}\n\
+        </style>\n\
+        <defs>\n{markers}</defs>\n\
+        <rect width=\"100%\" height=\"100%\" style=\"fill: {background}; stroke: none\"/>\n\
+        {body}</svg>\n"
+    )
+}
+
+/// Marker drawn for each set of heads, pointing right, to be turned to the direction of the line
+const HEADS: [(HeadGlyphs, &str); 5] = [
+    (HeadGlyphs::Arrow, "<path d=\"M 0 1 L 10 5 L 0 9 z\"/>"),
+    (HeadGlyphs::Triangle, "<path d=\"M 0 0 L 10 5 L 0 10 z\"/>"),
+    (
+        HeadGlyphs::Ascii,
+        "<path d=\"M 2 1 L 9 5 L 2 9\" style=\"fill: none\"/>",
+    ),
+    (
+        HeadGlyphs::Diamond,
+        "<path d=\"M 0 5 L 5 0 L 10 5 L 5 10 z\"/>",
+    ),
+    (HeadGlyphs::Circle, "<circle cx=\"5\" cy=\"5\" r=\"4\"/>"),
+];
+
+fn marker_id(glyphs: &HeadGlyphs) -> &'static str {
+    match glyphs {
+        HeadGlyphs::Arrow => "head-arrow",
+        HeadGlyphs::Triangle => "head-triangle",
+        HeadGlyphs::Ascii => "head-ascii",
+        HeadGlyphs::Diamond => "head-diamond",
+        HeadGlyphs::Circle => "head-circle",
+    }
+}
+
+fn svg_rectangle(body: &mut String, rect: &Rectangle, points: &[Point<i32>], origin: &Vec2<i32>) {
+    let (x, y) = centre(&Vec2::new(rect.x, rect.y), origin);
+    let width = (rect.width - 1) as f32 * CELL_WIDTH;
+    let height = (rect.height - 1) as f32 * CELL_HEIGHT;
+    let _ = writeln!(
+        body,
+        "<rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" rx=\"{}\"/>",
+        CELL_WIDTH / 2.0
+    );
+    let is_border = |p: &Vec2<i32>| {
+        p.x == rect.x
+            || p.y == rect.y
+            || p.x == rect.x + rect.width - 1
+            || p.y == rect.y + rect.height - 1
+    };
+    svg_text(body, points, origin, is_border);
+}
+
+fn svg_arrow(body: &mut String, arrow: &Arrow, points: &[Point<i32>], origin: &Vec2<i32>) {
+    if arrow.points.len() < 2 {
+        return;
+    }
+    let path = polyline_points(&arrow.points, origin);
+    let marker = format!("url(#{})", marker_id(&arrow.style.glyphs));
+    let mut markers = String::new();
+    if arrow.style.head.at_start() {
+        let _ = write!(markers, " marker-start=\"{marker}\"");
+    }
+    if arrow.style.head.at_end() {
+        let _ = write!(markers, " marker-end=\"{marker}\"");
+    }
+    let style = match arrow.style.line {
+        LineWeight::Light | LineWeight::Ascii => String::new(),
+        LineWeight::Heavy => format!(" style=\"stroke-width: {}\"", STROKE_WIDTH * 2.0),
+        LineWeight::Dashed => " style=\"stroke-dasharray: 6 4\"".to_string(),
+        LineWeight::Double => format!(" style=\"stroke-width: {}\"", STROKE_WIDTH * 3.0),
+    };
+    let _ = writeln!(body, "<polyline points=\"{path}\"{style}{markers}/>");
+    if arrow.style.line == LineWeight::Double {
+        // A thinner line in the background colour down the middle leaves two lines either side
+        let _ = writeln!(
+            body,
+            "<polyline points=\"{path}\" style=\"stroke: {}\"/>",
+            hex(Color::EmptyBackground)
+        );
+    }
+
+    let cells = arrow.cells();
+    if arrow.style.head == HeadPosition::Middle {
+        // Marker at the end of the half of the line leading up to the middle cell
+        let middle = cells.len() / 2;
+        let half = polyline_points(&cells[middle - 1..=middle], origin);
+        let _ = writeln!(
+            body,
+            "<polyline points=\"{half}\" style=\"stroke: none\" marker-end=\"{marker}\"/>"
+        );
+    }
+    svg_text(body, points, origin, |p| cells.contains(p));
+}
+
+fn polyline_points(points: &[Vec2<i32>], origin: &Vec2<i32>) -> String {
+    points
+        .iter()
+        .map(|p| {
+            let (x, y) = centre(p, origin);
+            format!("{x},{y}")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The text drawn in the cells that are not part of the outline of a shape, a line of text for
+/// every row. Every grapheme is placed in the middle of its cells so that the text lines up with
+/// the grid whatever font it is shown in
+fn svg_text(
+    body: &mut String,
+    points: &[Point<i32>],
+    origin: &Vec2<i32>,
+    is_outline: impl Fn(&Vec2<i32>) -> bool,
+) {
+    let mut rows: BTreeMap<i32, Vec<(i32, String)>> = BTreeMap::new();
+    let mut last = None;
+    for point in points.iter().filter(|p| !is_outline(&p.origin)) {
+        let row = rows.entry(point.y).or_default();
+        match row.last_mut() {
+            // Characters drawn in the same place as the one before are part of its grapheme
+            Some((_, grapheme)) if last.as_ref() == Some(&point.origin) => {
+                grapheme.push(point.character)
+            }
+            _ => row.push((point.x, point.character.to_string())),
+        }
+        last = Some(point.origin.clone());
+    }
+
+    for (y, graphemes) in rows {
+        let graphemes: Vec<_> = graphemes
+            .into_iter()
+            .filter(|(_, g)| !g.trim().is_empty())
+            .collect();
+        if graphemes.is_empty() {
+            continue;
+        }
+        // Positions are given for every UTF-16 code unit, only the first of each grapheme is used
+        let xs: Vec<String> = graphemes
+            .iter()
+            .flat_map(|(x, grapheme)| {
+                let (left, _) = centre(&Vec2::new(*x, y), origin);
+                let x = left + (grapheme.width().max(1) - 1) as f32 * CELL_WIDTH / 2.0;
+                std::iter::repeat_n(x.to_string(), grapheme.encode_utf16().count())
+            })
+            .collect();
+        let (_, y) = centre(&Vec2::new(0, y), origin);
+        let text: String = graphemes.iter().map(|(_, g)| escape(g)).collect();
+        let _ = writeln!(body, "<text x=\"{}\" y=\"{y}\">{text}</text>", xs.join(" "));
+    }
+}
+
+/// Middle of a cell, relative to the top left of the SVG
+fn centre(cell: &Vec2<i32>, origin: &Vec2<i32>) -> (f32, f32) {
+    (
+        ((cell.x - origin.x) as f32 + 0.5) * CELL_WIDTH,
+        ((cell.y - origin.y) as f32 + 0.5) * CELL_HEIGHT,
+    )
+}
+
+fn hex(color: Color) -> String {
+    let (r, g, b) = color.rgb();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        components::{arrow::Arrow, rectangle::Rectangle},
+        document::Document,
+        shape::Shape,
+        util::Vec2,
+    };
+
+    use crate::components::arrow_style::HeadPosition;
+
+    use super::{to_svg, to_text, Format};
+
+    #[test]
+    fn should_export_trimmed_text() {
+        let mut rect = Rectangle::new_at(10, 5);
+        rect.width = 6;
+        rect.height = 3;
+        rect.text = vec!['h', 'i'];
+        let mut arrow = Arrow::init();
+        arrow.points = vec![Vec2::new(15, 6), Vec2::new(20, 6), Vec2::new(20, 9)];
+        let document = Document {
+            shapes: vec![Shape::Rectangle(rect), Shape::Arrow(arrow)],
+            ..Default::default()
+        };
+
+        // Cut down to the drawing, with the junction where the arrow leaves the box
+        assert_eq!(
+            to_text(document).unwrap(),
+            "╭────╮\n\
+             │hi  ├───→┐\n\
+             ╰────╯    │\n\
+             \x20         │\n\
+             \x20         │\n"
+        );
+        assert_eq!(Format::parse("txt"), Some(Format::Txt));
+
+        // Wider than a renderer can be
+        let document = Document {
+            shapes: vec![
+                Shape::Rectangle(Rectangle::new_at(0, 0)),
+                Shape::Rectangle(Rectangle::new_at(70_000, 0)),
+            ],
+            ..Default::default()
+        };
+        assert!(to_text(document).is_err());
+    }
+
+    #[test]
+    fn should_export_svg() {
+        let mut rect = Rectangle::new_at(0, 0);
+        rect.width = 6;
+        rect.height = 3;
+        rect.text = vec!['a', '<', 'b'];
+        let mut arrow = Arrow::init();
+        arrow.points = vec![Vec2::new(5, 1), Vec2::new(10, 1), Vec2::new(10, 4)];
+        arrow.style.head = HeadPosition::End;
+        let document = Document {
+            shapes: vec![Shape::Rectangle(rect), Shape::Arrow(arrow)],
+            ..Default::default()
+        };
+
+        let svg = to_svg(&document).unwrap();
+        assert!(svg.contains(r#"<rect x="15" y="30" width="50" height="40" rx="5"/>"#));
+        assert!(svg.contains(r#"<text x="25 35 45" y="50">a&lt;b</text>"#));
+        assert!(svg.contains(
+            r#"<polyline points="65,50 115,50 115,110" marker-end="url(#head-arrow)"/>"#
+        ));
+    }
+}
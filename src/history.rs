@@ -0,0 +1,231 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{mutate::Mutate, state::StateChange};
+
+/// Every change made to the drawing, kept as a tree like vim's undo tree. Making a change after
+/// undoing starts a new branch, so the undone changes can still be gone back to by moving through
+/// the history in the order the changes were made.
+///
+/// Changes are numbered in the order they were made, starting from 1. State 0 is the drawing
+/// before any of them. Each change is kept as the mutation `M` that goes to the other side of it.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct History<M = StateChange> {
+    /// Change `n` is at index `n - 1`
+    changes: Vec<Change<M>>,
+    /// State the drawing is currently in
+    current: usize,
+    /// Change that redo goes to from state 0
+    first: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Change<M> {
+    /// State that the change was made in
+    parent: usize,
+    /// Undoes the change while it is applied, and redoes it while it is undone
+    mutation: M,
+    /// Seconds since the unix epoch when the change was made
+    time: u64,
+    /// Change that redo goes to from this one, the newest one made or gone through
+    next: Option<usize>,
+}
+
+impl<M: Default> History<M> {
+    /// Add a change that has just been made, `undo` being what reverts it
+    pub fn record(&mut self, undo: M) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.record_at(undo, time);
+    }
+
+    fn record_at(&mut self, undo: M, time: u64) {
+        self.changes.push(Change {
+            parent: self.current,
+            mutation: undo,
+            time,
+            next: None,
+        });
+        let n = self.changes.len();
+        self.set_next(self.current, n);
+        self.current = n;
+    }
+
+    /// State the drawing is currently in, which is different after any change is made or undone
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Revert the current change, returns false if there is nothing to undo
+    pub fn undo(&mut self, target: &mut impl Mutate<Mutation = M>) -> bool {
+        let n = self.current;
+        if n == 0 {
+            return false;
+        }
+        self.apply(n, target);
+        self.current = self.changes[n - 1].parent;
+        self.set_next(self.current, n);
+        true
+    }
+
+    /// Make the change that was last undone from the current state again, returns false if
+    /// there is nothing to redo
+    pub fn redo(&mut self, target: &mut impl Mutate<Mutation = M>) -> bool {
+        let next = match self.current {
+            0 => self.first,
+            n => self.changes[n - 1].next,
+        };
+        let Some(n) = next else {
+            return false;
+        };
+        self.apply(n, target);
+        self.current = n;
+        true
+    }
+
+    /// Go back or forward by a number of changes in the order they were made, whichever branch
+    /// they are on, like vim's `g-` and `g+`
+    pub fn step(&mut self, changes: i64, target: &mut impl Mutate<Mutation = M>) {
+        let n = (self.current as i64)
+            .saturating_add(changes)
+            .clamp(0, self.changes.len() as i64);
+        self.go_to(n as usize, target);
+    }
+
+    /// Go to the drawing as it was a number of seconds before or after the current state, like
+    /// vim's `:earlier 5m` and `:later 5m`
+    pub fn travel(&mut self, seconds: i64, target: &mut impl Mutate<Mutation = M>) {
+        let now = match self.current {
+            0 => self.changes.first().map_or(0, |c| c.time),
+            n => self.changes[n - 1].time,
+        };
+        let goal = (now as i64).saturating_add(seconds);
+        let n = self
+            .changes
+            .iter()
+            .rposition(|c| c.time as i64 <= goal)
+            .map_or(0, |i| i + 1);
+        // Going forward never stops before the change it started at
+        let n = match seconds > 0 {
+            true => n.max(self.current),
+            false => n,
+        };
+        self.go_to(n, target);
+    }
+
+    /// Undo back to where the branch of state `n` splits off from the current one, then redo
+    /// along it
+    fn go_to(&mut self, n: usize, target: &mut impl Mutate<Mutation = M>) {
+        let mut path = vec![n];
+        while let Some(&last) = path.last().filter(|last| **last != 0) {
+            path.push(self.changes[last - 1].parent);
+        }
+        while !path.contains(&self.current) {
+            self.undo(target);
+        }
+        let split = path.iter().position(|p| *p == self.current).unwrap_or(0);
+        for &next in path[..split].iter().rev() {
+            self.set_next(self.current, next);
+            self.redo(target);
+        }
+    }
+
+    /// Apply the mutation of change `n`, keeping the mutation that reverses it
+    fn apply(&mut self, n: usize, target: &mut impl Mutate<Mutation = M>) {
+        let change = &mut self.changes[n - 1];
+        let mutation = std::mem::take(&mut change.mutation);
+        change.mutation = target.mutate(mutation);
+    }
+
+    fn set_next(&mut self, state: usize, next: usize) {
+        match state {
+            0 => self.first = Some(next),
+            n => self.changes[n - 1].next = Some(next),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mutate::Mutate;
+
+    use super::History;
+
+    /// Changes to a list of numbers, which is enough to see what order changes were applied in
+    #[derive(Default)]
+    enum Edit {
+        Push(usize),
+        #[default]
+        Pop,
+    }
+
+    #[derive(Default)]
+    struct Numbers(Vec<usize>);
+
+    impl Mutate for Numbers {
+        type Mutation = Edit;
+        fn mutate(&mut self, mx: Edit) -> Edit {
+            match mx {
+                Edit::Push(n) => {
+                    self.0.push(n);
+                    Edit::Pop
+                }
+                Edit::Pop => Edit::Push(self.0.pop().unwrap()),
+            }
+        }
+    }
+
+    /// Make a change that pushes `n`, recording how to undo it
+    fn push(history: &mut History<Edit>, numbers: &mut Numbers, n: usize, time: u64) {
+        let undo = numbers.mutate(Edit::Push(n));
+        history.record_at(undo, time);
+    }
+    #[test]
+    fn should_keep_undone_branch() {
+        let mut history = History::default();
+        let mut numbers = Numbers::default();
+        push(&mut history, &mut numbers, 1, 0);
+        push(&mut history, &mut numbers, 2, 0);
+        history.undo(&mut numbers);
+        push(&mut history, &mut numbers, 3, 0);
+        assert_eq!(numbers.0, vec![1, 3]);
+        assert!(!history.redo(&mut numbers));
+
+        // Change 2 is on the other branch, but comes just before change 3
+        history.step(-1, &mut numbers);
+        assert_eq!(numbers.0, vec![1, 2]);
+        history.step(-2, &mut numbers);
+        assert!(numbers.0.is_empty());
+        history.redo(&mut numbers);
+        history.redo(&mut numbers);
+        assert_eq!(numbers.0, vec![1, 2]);
+        history.step(1, &mut numbers);
+        assert_eq!(numbers.0, vec![1, 3]);
+    }
+
+    #[test]
+    fn should_travel_by_time() {
+        let mut history = History::default();
+        let mut numbers = Numbers::default();
+        push(&mut history, &mut numbers, 1, 100);
+        push(&mut history, &mut numbers, 2, 200);
+        push(&mut history, &mut numbers, 3, 400);
+
+        history.travel(-150, &mut numbers);
+        assert_eq!(numbers.0, vec![1, 2]);
+        history.travel(-150, &mut numbers);
+        assert!(numbers.0.is_empty());
+        history.travel(150, &mut numbers);
+        assert_eq!(numbers.0, vec![1, 2]);
+        history.travel(1000, &mut numbers);
+        assert_eq!(numbers.0, vec![1, 2, 3]);
+
+        // Going further than there is history stops at either end
+        history.travel(-i64::MAX, &mut numbers);
+        assert!(numbers.0.is_empty());
+        history.step(i64::MAX, &mut numbers);
+        assert_eq!(numbers.0, vec![1, 2, 3]);
+    }
+}
@@ -1,14 +1,21 @@
-use std::io::stdout;
+use std::{
+    io::{stdout, Write},
+    panic::{self, AssertUnwindSafe},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
 use components::{
     debug_panel::{DebugPanel, DEBUG_PANEL_HEIGHT},
     grid_background::GridBackground,
     intersections::Intersections,
-    rectangle::Drag,
     status_bar::StatusBar,
 };
 use crossterm::{
-    event::{self, KeyCode, KeyModifiers},
+    event::{
+        self, KeyCode, KeyModifiers, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
     execute,
     style::{Color, ResetColor, SetForegroundColor},
     terminal::{self, disable_raw_mode, enable_raw_mode},
@@ -16,19 +23,24 @@ use crossterm::{
 use cursor::{cursor_position, set_position};
 use cursor_guide::CursorGuide;
 use draw::{Draw, DrawSticky};
-use mode::{Anchor, Mode};
+use mode::Mode;
 use motion_state::MotionState;
-use persistence::{load, save};
+use persistence::{load, load_history, load_swap, remove_swap};
 use renderer::Renderer;
 use shape::Shape;
+use signals::StopSignals;
 use state::State;
 use util::yank::handle_yank;
 
 mod characters;
+mod command;
 mod components;
 mod cursor;
 mod cursor_guide;
+mod document;
 mod draw;
+mod export;
+mod history;
 mod mode;
 mod motion_state;
 mod mutate;
@@ -36,38 +48,135 @@ mod persistence;
 mod renderer;
 mod shape;
 mod shape_id;
+mod signals;
 mod state;
 mod util;
+mod viewport;
+
+/// Longest time to wait for a key before checking for signals and autosaving
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 fn main() -> std::io::Result<()> {
-    init()?;
-    let mut state = State::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "export") {
+        if let Err(e) = export::run(&args[1..]) {
+            eprintln!("traw export: {e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let mut motion_state = MotionState::new();
-    let (width, height) = terminal::size()?;
-    let mut renderer = Renderer::new(width, height);
-    let path_arg = std::env::args().nth(1);
+    let (flags, paths): (Vec<String>, Vec<String>) =
+        args.into_iter().partition(|arg| arg.starts_with("--"));
+    let undofile = flags.iter().any(|flag| flag == "--undofile");
+
+    let mut state = State::init();
+    if let Some(path) = paths.into_iter().next() {
+        let document = load(&path)?;
+        let history = undofile.then(|| load_history(&path)).flatten();
+        state = State::open(document);
+        state.open_history(history.unwrap_or_default());
+        // Recovery is only offered for a drawing that was asked for, not for whatever was left
+        // behind by an earlier session without a file name
+        if let Some(document) = load_swap(&path) {
+            if ask_to_recover(&path)? {
+                state.recover(document);
+            }
+        }
+        state.file_name = path;
+    }
+    state.undofile = undofile;
 
-    let mut file_name = "unnamed.traw".to_string();
+    // The file is loaded before taking over the terminal, so that errors can be read
+    init()?;
+    restore_terminal_on_panic();
+    let mut stop_signals = StopSignals::register()?;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        run(&mut state, &mut motion_state, &mut stop_signals)
+    }));
+    let _ = cleanup();
 
-    if let Some(path) = path_arg {
-        file_name = path;
-        state = load(&file_name)?;
+    let (reason, code) = match result {
+        Ok(Ok(None)) => {
+            remove_swap(&state.file_name);
+            return Ok(());
+        }
+        Ok(Ok(Some(signal))) => (
+            format!("traw was stopped by {}", signals::name(signal)),
+            128 + signal,
+        ),
+        Ok(Err(e)) => (format!("traw stopped because of an error: {e}"), 1),
+        // The panic message has already been printed by the panic hook
+        Err(_) => ("traw crashed".to_string(), 101),
+    };
+    eprintln!("{reason}");
+    match state.save_for_recovery() {
+        Some(Ok(path)) => eprintln!(
+            "Unsaved changes were written to {path}, open {} again to recover them",
+            state.file_name
+        ),
+        Some(Err(e)) => eprintln!("Unsaved changes could not be saved: {e}"),
+        None => {}
     }
+    std::process::exit(code);
+}
+
+/// Edit until told to quit, returns the signal that stopped it early if there was one
+fn run(
+    state: &mut State,
+    motion_state: &mut MotionState,
+    signals: &mut StopSignals,
+) -> std::io::Result<Option<i32>> {
+    let (width, height) = terminal::size()?;
+    let mut renderer = Renderer::new(width, height);
+    state.viewport.resize(width, height);
 
     set_position((5, 2).into());
 
-    render(&mut renderer, &mut state)?;
+    render(&mut renderer, state)?;
 
     loop {
-        if let event::Event::Key(key_event) = event::read()? {
+        if let Some(signal) = signals.received() {
+            return Ok(Some(signal));
+        }
+        // Waiting for a key times out, so that signals are noticed and changes are written to
+        // the recovery file even when nothing is being pressed
+        if !event::poll(POLL_INTERVAL)? {
+            if state.autosave() {
+                render(&mut renderer, state)?;
+            }
+            continue;
+        }
+        let event = event::read()?;
+        if let event::Event::Resize(width, height) = event {
+            handle_resize(&mut renderer, state, width, height)?;
+        }
+        if let event::Event::Key(key_event) = event {
+            state.message = None;
             match key_event.code {
+                KeyCode::Char(key @ ('h' | 'j' | 'k' | 'l'))
+                    if key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    motion_state.handle_pan(key, &mut state.viewport);
+                }
+                KeyCode::Char('z') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    suspend(&mut renderer, state)?;
+                }
+                KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    state.handle_escape()?;
+                }
+                _ if state.handle_text_key(&key_event)? => {}
                 KeyCode::Char(key) => match &mut state.mode {
-                    Mode::Text(rect) => {
-                        rect.on_char(key)?;
+                    Mode::Command(_) => {
+                        state.handle_char(key)?;
                     }
                     Mode::Normal => match key {
-                        'q' => break,
-                        's' => save(&state, &file_name)?,
+                        _ if state.handle_history_key(key) => {}
+                        'q' => state.quit(false),
+                        's' => {
+                            state.save();
+                        }
                         'i' => state.handle_insert()?,
                         'r' => {
                             if key_event.modifiers.contains(KeyModifiers::CONTROL) {
@@ -78,40 +187,111 @@ fn main() -> std::io::Result<()> {
                         }
                         'x' => state.handle_delete()?,
                         'v' => state.handle_select()?,
+                        'm' => state.handle_move()?,
+                        'a' => state.handle_route()?,
+                        'H' | 'G' | 'L' | 'B' | 'A' | 'V' | '>' | '<' | 'S' | 'F' => {
+                            state.handle_restyle(key)?
+                        }
+                        ':' => state.handle_command_line()?,
                         'd' => state.debug_enabled = !state.debug_enabled,
                         'u' => state.undo(),
-                        _ => motion_state.handle_motions(key, &renderer, &state.mode)?,
+                        _ => motion_state.handle_motions(
+                            key,
+                            &renderer,
+                            &state.mode,
+                            &mut state.viewport,
+                        )?,
                     },
-                    Mode::Select(selection) => {
+                    Mode::Select(_) => {
                         if key == 'y' {
-                            handle_yank(&renderer, selection);
+                            handle_yank(state)?;
+                        }
+                        if key == 'm' {
+                            state.handle_move()?;
+                        } else {
+                            motion_state.handle_motions(
+                                key,
+                                &renderer,
+                                &state.mode,
+                                &mut state.viewport,
+                            )?;
+                        }
+                    }
+                    Mode::EditArrow(_) => {
+                        if !state.handle_arrow_edit(key)? {
+                            motion_state.handle_motions(
+                                key,
+                                &renderer,
+                                &state.mode,
+                                &mut state.viewport,
+                            )?;
                         }
-                        motion_state.handle_motions(key, &renderer, &state.mode)?;
                     }
                     _ => {
-                        motion_state.handle_motions(key, &renderer, &state.mode)?;
+                        motion_state.handle_motions(
+                            key,
+                            &renderer,
+                            &state.mode,
+                            &mut state.viewport,
+                        )?;
                     }
                 },
 
                 KeyCode::Enter => {
-                    if let Mode::Select(selection) = &state.mode {
-                        handle_yank(&renderer, selection);
-                    }
+                    handle_yank(state)?;
                     state.handle_enter()?;
                 }
                 KeyCode::Backspace => state.handle_backspace()?,
+                KeyCode::Esc => state.handle_escape()?,
                 _ => {}
             }
         }
 
-        render(&mut renderer, &mut state)?;
+        if state.should_quit {
+            return Ok(None);
+        }
+        state.autosave();
+
+        render(&mut renderer, state)?;
     }
+}
+
+/// Ask on the command line, before the editor starts, whether to use the recovery file left
+/// behind when traw last edited the drawing without exiting cleanly
+fn ask_to_recover(file_name: &str) -> std::io::Result<bool> {
+    print!(
+        "Found unsaved changes to {file_name} from a session that did not exit cleanly.\n\
+        Recover them? Otherwise they are lost when traw next exits [y/N] "
+    );
+    stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
 
+/// Give the terminal back to the shell like Ctrl-Z usually does, then take it over again and draw
+/// everything from scratch once resumed
+fn suspend(renderer: &mut Renderer, state: &mut State) -> std::io::Result<()> {
     cleanup()?;
+    signals::suspend()?;
+    init()?;
+    let (width, height) = terminal::size()?;
+    handle_resize(renderer, state, width, height)
+}
 
-    Ok(())
+/// Give the terminal back before the panic message is printed, so that it can be read
+fn restore_terminal_on_panic() {
+    let print_panic = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = cleanup();
+        print_panic(info);
+    }));
 }
 
+/// Whether the terminal was asked to report keys it otherwise can't tell apart, like SHIFT-enter
+/// from enter, which has to be undone when giving the terminal back
+static KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+
 fn init() -> std::io::Result<()> {
     enable_raw_mode()?;
     execute!(
@@ -121,22 +301,59 @@ fn init() -> std::io::Result<()> {
         SetForegroundColor(Color::White),
         ResetColor
     )?;
+    if terminal::supports_keyboard_enhancement().unwrap_or(false) {
+        execute!(
+            stdout(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        )?;
+        KEYBOARD_ENHANCED.store(true, Ordering::Relaxed);
+    }
 
     Ok(())
 }
 
 fn cleanup() -> std::io::Result<()> {
-    execute!(stdout(), terminal::LeaveAlternateScreen)?;
+    if KEYBOARD_ENHANCED.swap(false, Ordering::Relaxed) {
+        execute!(stdout(), PopKeyboardEnhancementFlags)?;
+    }
+    execute!(
+        stdout(),
+        terminal::LeaveAlternateScreen,
+        crossterm::cursor::SetCursorStyle::DefaultUserShape
+    )?;
     disable_raw_mode()?;
 
     Ok(())
 }
 
+fn handle_resize(
+    renderer: &mut Renderer,
+    state: &mut State,
+    width: u16,
+    height: u16,
+) -> std::io::Result<()> {
+    execute!(stdout(), terminal::Clear(terminal::ClearType::All))?;
+    renderer.resize(width, height);
+    state.viewport.resize(width, height);
+    // Keep the cursor on screen if the terminal got smaller
+    let cursor = cursor_position();
+    set_position(
+        (
+            cursor.x.min(width.saturating_sub(1)),
+            cursor.y.min(height.saturating_sub(1)),
+        )
+            .into(),
+    );
+    Ok(())
+}
+
 fn render(renderer: &mut Renderer, state: &mut State) -> std::io::Result<()> {
-    renderer.render_frame(|r| {
-        r.render(GridBackground::new().draw()?, None)?;
+    state.follow_cursor()?;
+    let cursor = state.viewport.cursor_position();
+    renderer.render_frame(&state.viewport.offset.clone(), |r| {
+        r.render(GridBackground::new(&state.viewport).draw()?, None)?;
         r.render_sticky(
-            StatusBar::new(&state.mode, {
+            StatusBar::new(state, &cursor, {
                 if state.debug_enabled {
                     DEBUG_PANEL_HEIGHT as u16
                 } else {
@@ -148,8 +365,11 @@ fn render(renderer: &mut Renderer, state: &mut State) -> std::io::Result<()> {
         if state.debug_enabled {
             r.render_sticky(DebugPanel {}.draw()?)?;
         }
-        r.render(CursorGuide::new(&state.shapes).draw()?, None)?;
-        for shape in &state.shapes {
+        r.render(
+            CursorGuide::new(&state.document.shapes, cursor.clone()).draw()?,
+            None,
+        )?;
+        for shape in &state.document.shapes {
             r.render(
                 shape.draw()?,
                 match shape {
@@ -160,21 +380,24 @@ fn render(renderer: &mut Renderer, state: &mut State) -> std::io::Result<()> {
         }
         match &mut state.mode {
             Mode::Normal => {}
-            Mode::DrawRectangle(rect, anchor) => {
-                rect.drag_corner(anchor)?;
+            Mode::DrawRectangle(rect, _) => {
                 r.render(rect.draw()?, Some(rect.shape_id))?;
             }
-            Mode::Text(rect) => {
-                r.render(rect.draw()?, Some(rect.shape_id))?;
+            Mode::Text(edit) => {
+                r.render(edit.target.draw()?, Some(edit.target.shape_id))?;
             }
             Mode::DrawArrow(arrow) => {
-                arrow.update(cursor_position().into());
                 r.render(arrow.draw()?, Some(arrow.shape_id))?;
             }
             Mode::Select(selection) => {
-                selection.drag_corner(&mut Anchor::BottomRight)?;
                 r.render_overlay(selection)?;
             }
+            Mode::Route(routing) => {
+                if let Some(arrow) = &routing.preview {
+                    r.render(arrow.draw()?, Some(arrow.shape_id))?;
+                }
+            }
+            Mode::Move(_) | Mode::EditArrow(_) | Mode::Label(_) | Mode::Command(_) => {}
         }
         r.render(Intersections::new(state).draw()?, None)?;
         r.render_overlay(state)?;
@@ -1,23 +1,52 @@
 use std::io::Result;
 
-use serde::{Deserialize, Serialize};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 use crate::{
     components::{
-        arrow::Arrow,
+        arrow::{Arrow, ArrowHandle, Label},
         rectangle::{Drag, Rectangle},
     },
-    draw::{Color, DrawOverlay, OverlayPoint},
+    draw::{Color, DrawOverlay, OverlayPoint, Point},
+    util::{
+        text::{
+            next_grapheme, next_word_start, prev_grapheme, prev_word_start, run_end, word_end,
+            EditText,
+        },
+        Vec2,
+    },
 };
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+/// What the editor is doing, which decides what keys do. Every mode is entered from normal mode
+/// and goes back to it, 'enter' keeps what was done and 'esc' or 'CTRL-c' throw it away:
+///
+/// | Mode          | Entered with          | 'enter'                       | 'esc'                     |
+/// |---------------|-----------------------|-------------------------------|---------------------------|
+/// | DrawRectangle | 'i' in space, 'r'     | Text for a new box, or Normal | Normal, box restored      |
+/// | Text          | 'i' in a box, new box | Normal, text kept             | Normal, box restored      |
+/// | DrawArrow     | 'i' on a box border   | Normal, arrow added           | Normal, arrow dropped     |
+/// | Select        | 'v'                   | Normal, selection yanked      | Normal                    |
+/// | Move          | 'm'                   | Normal, shapes stay           | Normal, shapes moved back |
+/// | Route         | 'a' on a box          | Normal, arrow added           | Normal                    |
+/// | EditArrow     | 'i' on an arrow       | Normal, grabbed part let go   | Normal, grab undone       |
+/// | Label         | 't' in EditArrow      | Normal, label kept            | Normal, label restored    |
+/// | Command       | ':'                   | Normal, command run           | Normal                    |
+///
+/// In text and label mode 'esc' only switches to the normal sub-mode, so only 'CTRL-c' cancels.
+#[derive(Debug, Default)]
 pub enum Mode {
     #[default]
     Normal,
     DrawRectangle(Rectangle, Anchor),
     DrawArrow(Arrow),
     Select(Selection),
-    Text(Rectangle),
+    Text(TextEdit),
+    Move(Movement),
+    Route(Routing),
+    EditArrow(ArrowEdit),
+    Label(LabelEdit),
+    /// Typing a command after ':'
+    Command(String),
 }
 
 impl Mode {
@@ -27,7 +56,7 @@ impl Mode {
 }
 
 /// When resizing a rectangle, which corner is being dragged
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Debug)]
 pub enum Anchor {
     TopLeft,
     TopRight,
@@ -35,7 +64,7 @@ pub enum Anchor {
     BottomLeft,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct Selection {
     pub x: i32,
     pub y: i32,
@@ -43,6 +72,20 @@ pub struct Selection {
     pub height: i32,
 }
 
+impl Selection {
+    pub fn contains(&self, point: &Vec2<i32>) -> bool {
+        point.x >= self.x
+            && point.x < self.x + self.width
+            && point.y >= self.y
+            && point.y < self.y + self.height
+    }
+
+    /// Whether every drawn point of a shape is inside of the selection
+    pub fn contains_all(&self, points: &[Point<i32>]) -> bool {
+        points.iter().all(|p| self.contains(p))
+    }
+}
+
 impl Drag for Selection {
     fn rect(&mut self) -> (&mut i32, &mut i32, &mut i32, &mut i32) {
         (&mut self.x, &mut self.y, &mut self.width, &mut self.height)
@@ -66,3 +109,299 @@ impl DrawOverlay for Selection {
         Ok((points, None, background))
     }
 }
+
+/// Shapes that follow the cursor while moving, `current` is the cursor position they were last
+/// moved to
+#[derive(Debug)]
+pub struct Movement {
+    pub shape_ids: Vec<u32>,
+    pub start: Vec2<i32>,
+    pub current: Vec2<i32>,
+}
+
+impl Movement {
+    pub fn new(shape_ids: Vec<u32>, cursor: Vec2<i32>) -> Self {
+        Self {
+            shape_ids,
+            start: cursor.clone(),
+            current: cursor,
+        }
+    }
+
+    /// How far the shapes need to move to catch up with the cursor
+    pub fn update(&mut self, cursor: &Vec2<i32>) -> Vec2<i32> {
+        let delta = cursor.clone() - self.current.clone();
+        self.current = cursor.clone();
+        delta
+    }
+}
+
+/// Picking the box that a routed arrow should go to. `preview` is the route to the box under the
+/// cursor, if there is one, and is only found again when the cursor moves onto another box
+#[derive(Debug)]
+pub struct Routing {
+    pub source: u32,
+    pub target: Option<u32>,
+    pub preview: Option<Arrow>,
+}
+
+/// Editing the corners and segments of an existing arrow, which stays in the list of shapes
+#[derive(Debug)]
+pub struct ArrowEdit {
+    pub shape_id: u32,
+    pub grab: Option<Grab>,
+}
+
+/// Part of an arrow that is being dragged around by the cursor
+#[derive(Debug)]
+pub struct Grab {
+    pub handle: ArrowHandle,
+    /// Points to apply the drag to, which can have extra corners compared to the original
+    pub points: Vec<Vec2<i32>>,
+    /// The arrow before the drag started, for undoing
+    pub original: Arrow,
+    pub start: Vec2<i32>,
+}
+
+/// Typing the label of an arrow. The arrow is edited in place, with the label copied into it after
+/// every key, `original` is kept so the whole edit can be undone at once
+#[derive(Debug)]
+pub struct LabelEdit {
+    pub shape_id: u32,
+    pub original: Arrow,
+    pub text: TextEdit<Label>,
+}
+
+/// Typing the text of a box or the label of an arrow. `cursor` is the index in the text that
+/// characters are inserted before. In the normal sub-mode keys are vim commands for moving around
+/// and editing the text instead of being typed
+#[derive(Debug)]
+pub struct TextEdit<T = Rectangle> {
+    pub target: T,
+    pub cursor: usize,
+    pub normal: bool,
+    /// Operator waiting for a motion, like the `d` in `dw`
+    pending: Option<char>,
+}
+
+impl<T: EditText> TextEdit<T> {
+    pub fn new(target: T) -> Self {
+        Self {
+            cursor: target.text().len(),
+            target,
+            normal: false,
+            pending: None,
+        }
+    }
+
+    /// Returns false if the key is not used for editing text
+    pub fn handle_key(&mut self, key: &KeyEvent) -> bool {
+        let text = self.target.text();
+        let (prev, next) = (
+            prev_grapheme(text, self.cursor),
+            next_grapheme(text, self.cursor),
+        );
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Left => self.cursor = prev,
+            KeyCode::Right => self.cursor = next,
+            KeyCode::Up => self.cursor = self.target.move_vertically(self.cursor, -1),
+            KeyCode::Down => self.cursor = self.target.move_vertically(self.cursor, 1),
+            KeyCode::Home => self.cursor = self.target.line_range(self.cursor).start,
+            KeyCode::End => self.cursor = self.target.line_range(self.cursor).end,
+            KeyCode::Delete => self.delete(self.cursor, next),
+            // Like in vim, pressing it again in the normal sub-mode does nothing, so that the text
+            // is not lost by pressing it one time too many
+            KeyCode::Esc => {
+                self.normal = true;
+                self.pending = None;
+            }
+            KeyCode::Enter
+                if !self.normal
+                    && self.target.is_multiline()
+                    && key
+                        .modifiers
+                        .intersects(KeyModifiers::SHIFT | KeyModifiers::ALT) =>
+            {
+                self.insert('\n')
+            }
+            KeyCode::Backspace if self.normal => self.cursor = prev,
+            KeyCode::Backspace => self.delete(prev, self.cursor),
+            KeyCode::Char('w') if control && !self.normal => {
+                let start = prev_word_start(self.target.text(), self.cursor);
+                self.delete(start, self.cursor);
+            }
+            KeyCode::Char(_) if control => return false,
+            KeyCode::Char(c) if self.normal => self.handle_command(c),
+            KeyCode::Char(c) => self.insert(c),
+            _ => return false,
+        }
+        true
+    }
+
+    fn handle_command(&mut self, c: char) {
+        let text = self.target.text();
+        let cursor = self.cursor;
+        match (self.pending.take(), c) {
+            (Some('d'), 'w') => {
+                // Like vim, deleting a word stops at the end of the line
+                let end = next_word_start(text, cursor);
+                let line_end = (cursor..end).find(|i| text[*i] == '\n' && *i > cursor);
+                self.delete(cursor, line_end.unwrap_or(end));
+            }
+            (Some('c'), 'w') => {
+                // Changing a word leaves the space after it, unlike deleting
+                let end = match text.get(cursor) {
+                    Some(c) if !c.is_whitespace() => run_end(text, cursor),
+                    _ => next_word_start(text, cursor),
+                };
+                self.delete(cursor, end);
+                self.normal = false;
+            }
+            (Some('d'), 'd') => {
+                let start = text[..cursor]
+                    .iter()
+                    .rposition(|c| *c == '\n')
+                    .map_or(0, |i| i + 1);
+                let end = text[cursor..]
+                    .iter()
+                    .position(|c| *c == '\n')
+                    .map_or(text.len(), |i| cursor + i);
+                // Take one of the newlines around the line with it
+                match (end < text.len(), start > 0) {
+                    (true, _) => self.delete(start, end + 1),
+                    (false, true) => self.delete(start - 1, end),
+                    (false, false) => self.delete(start, end),
+                }
+            }
+            (Some(_), _) => {}
+            (None, 'd' | 'c') => self.pending = Some(c),
+            (None, 'h') => self.cursor = prev_grapheme(text, cursor),
+            (None, 'l') => self.cursor = next_grapheme(text, cursor),
+            (None, 'j') => self.cursor = self.target.move_vertically(cursor, 1),
+            (None, 'k') => self.cursor = self.target.move_vertically(cursor, -1),
+            (None, 'w') => self.cursor = next_word_start(text, cursor),
+            (None, 'b') => self.cursor = prev_word_start(text, cursor),
+            (None, 'e') => self.cursor = prev_grapheme(text, word_end(text, cursor) + 1),
+            (None, '0') => self.cursor = self.target.line_range(cursor).start,
+            (None, '$') => self.cursor = self.target.line_range(cursor).end,
+            (None, 'x') => self.delete(cursor, next_grapheme(text, cursor)),
+            (None, 'i') => self.normal = false,
+            (None, 'a') => {
+                self.cursor = next_grapheme(text, cursor);
+                self.normal = false;
+            }
+            (None, 'I') => {
+                self.cursor = self.target.line_range(cursor).start;
+                self.normal = false;
+            }
+            (None, 'A') => {
+                self.cursor = self.target.line_range(cursor).end;
+                self.normal = false;
+            }
+            (None, _) => {}
+        }
+    }
+
+    fn insert(&mut self, c: char) {
+        self.target.text_mut().insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    /// Remove the text between two indices, and put the cursor where it was
+    fn delete(&mut self, start: usize, end: usize) {
+        let text = self.target.text_mut();
+        let end = end.min(text.len());
+        if start < end {
+            text.drain(start..end);
+        }
+        self.cursor = start.min(text.len());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use crate::{
+        components::{arrow::Label, rectangle::Rectangle},
+        util::text::EditText,
+    };
+
+    use super::TextEdit;
+
+    fn text_edit(text: &str) -> TextEdit {
+        let mut rect = Rectangle::new_at(0, 0);
+        rect.width = 20;
+        rect.height = 5;
+        rect.text = text.chars().collect();
+        TextEdit::new(rect)
+    }
+
+    fn press<T: EditText>(edit: &mut TextEdit<T>, keys: &[KeyCode]) {
+        for key in keys {
+            edit.handle_key(&KeyEvent::new(*key, KeyModifiers::NONE));
+        }
+    }
+
+    fn type_keys<T: EditText>(edit: &mut TextEdit<T>, keys: &str) {
+        press(edit, &keys.chars().map(KeyCode::Char).collect::<Vec<_>>());
+    }
+
+    fn text(edit: &TextEdit) -> String {
+        edit.target.text.iter().collect()
+    }
+
+    #[test]
+    fn should_insert_and_delete_at_cursor() {
+        let mut edit = text_edit("helo");
+        press(&mut edit, &[KeyCode::Left, KeyCode::Left]);
+        type_keys(&mut edit, "l");
+        assert_eq!(text(&edit), "hello");
+        press(&mut edit, &[KeyCode::Home, KeyCode::Delete]);
+        assert_eq!(text(&edit), "ello");
+        press(&mut edit, &[KeyCode::End]);
+        edit.handle_key(&KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL));
+        assert_eq!(text(&edit), "");
+    }
+
+    #[test]
+    fn should_edit_words_in_normal_mode() {
+        let mut edit = text_edit("one two three");
+        press(&mut edit, &[KeyCode::Esc]);
+        type_keys(&mut edit, "0wdw");
+        assert_eq!(text(&edit), "one three");
+        type_keys(&mut edit, "cwfour");
+        assert_eq!(text(&edit), "one four");
+        assert!(!edit.normal);
+    }
+
+    #[test]
+    fn should_delete_line_in_normal_mode() {
+        let mut edit = text_edit("one\ntwo\nthree");
+        press(&mut edit, &[KeyCode::Esc, KeyCode::Up]);
+        type_keys(&mut edit, "dd");
+        assert_eq!(text(&edit), "one\nthree");
+    }
+
+    #[test]
+    fn should_keep_text_on_repeated_escape() {
+        let mut edit = text_edit("one");
+        press(&mut edit, &[KeyCode::Esc]);
+        let escape = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        assert!(edit.handle_key(&escape));
+        assert!(edit.normal);
+        assert_eq!(text(&edit), "one");
+    }
+
+    #[test]
+    fn should_keep_labels_on_one_line() {
+        let mut edit = TextEdit::new(Label::default());
+        type_keys(&mut edit, "ab");
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT);
+        assert!(!edit.handle_key(&enter));
+        press(&mut edit, &[KeyCode::Home]);
+        type_keys(&mut edit, "c");
+        assert_eq!(edit.target.text, vec!['c', 'a', 'b']);
+    }
+}
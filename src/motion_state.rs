@@ -1,19 +1,25 @@
 use std::cmp::Ordering;
 
 use crate::{
-    cursor::{adjust_position, cursor_position, set_position},
+    cursor::{cursor_position, set_position},
     mode::Mode,
     renderer::Renderer,
     util::Vec2,
+    viewport::Viewport,
 };
 
 pub struct MotionState {
     count: Vec<char>,
+    /// First key of a two key command, e.g. the first 'z' of 'zz'
+    pending: Option<char>,
 }
 
 impl MotionState {
     pub fn new() -> Self {
-        Self { count: vec![] }
+        Self {
+            count: vec![],
+            pending: None,
+        }
     }
 
     pub fn handle_motions(
@@ -21,36 +27,23 @@ impl MotionState {
         key: char,
         renderer: &Renderer,
         mode: &Mode,
+        viewport: &mut Viewport,
     ) -> std::io::Result<()> {
-        let move_count = self.get_count() as i16;
-        match key {
-            'h' => {
-                adjust_position((-move_count, 0).into());
-            }
-            'j' => {
-                adjust_position((0, move_count).into());
-            }
-            'k' => {
-                adjust_position((0, -move_count).into());
-            }
-            'l' => {
-                adjust_position((move_count, 0).into());
+        if let Some(pending) = self.pending.take() {
+            if let ('z', 'z') = (pending, key) {
+                viewport.center_on_cursor();
             }
-            'w' => {
-                if mode.is_normal() {
-                    word_motion(renderer, get_next_word_start);
-                }
-            }
-            'b' => {
-                if mode.is_normal() {
-                    word_motion(renderer, get_previous_word_start);
-                }
-            }
-            'e' => {
-                if mode.is_normal() {
-                    word_motion(renderer, get_next_word_end);
-                }
+            return Ok(());
+        }
+        match key {
+            'h' | 'j' | 'k' | 'l' => {
+                let target = viewport.cursor_position() + self.get_direction(key);
+                viewport.set_cursor_position(target);
             }
+            'w' if mode.is_normal() => word_motion(renderer, get_next_word_start),
+            'b' if mode.is_normal() => word_motion(renderer, get_previous_word_start),
+            'e' if mode.is_normal() => word_motion(renderer, get_next_word_end),
+            'z' => self.pending = Some(key),
             '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9' => self.count.push(key),
             _ => {}
         }
@@ -58,6 +51,25 @@ impl MotionState {
         Ok(())
     }
 
+    /// Scroll the viewport without moving the cursor on the canvas
+    pub fn handle_pan(&mut self, key: char, viewport: &mut Viewport) {
+        let cursor = viewport.cursor_position();
+        viewport.pan(self.get_direction(key));
+        viewport.set_cursor_position(cursor);
+    }
+
+    fn get_direction(&mut self, key: char) -> Vec2<i32> {
+        let count = self.get_count() as i32;
+        match key {
+            'h' => (-count, 0),
+            'j' => (0, count),
+            'k' => (0, -count),
+            'l' => (count, 0),
+            _ => (0, 0),
+        }
+        .into()
+    }
+
     fn get_count(&mut self) -> u16 {
         let chars = std::mem::take(&mut self.count);
         chars.iter().collect::<String>().parse::<u16>().unwrap_or(1)
@@ -97,13 +109,9 @@ fn get_next_word_start(cursor_position: Vec2<u16>, points: ShapePositions) -> Ju
 
     let jump_to = points.iter().enumerate().find(|(i, (position, shape_id))| {
         Ordering::is_gt(position.cmp(&cursor_position))
-            && shape_id.map_or(false, |id| {
-                i.checked_sub(1).map_or(false, |i| {
-                    points
-                        .get(i)
-                        .and_then(|(_, id)| *id)
-                        .map_or(true, |n| n != id)
-                })
+            && shape_id.is_some_and(|id| {
+                i.checked_sub(1)
+                    .is_some_and(|i| points.get(i).and_then(|(_, id)| *id) != Some(id))
             })
     });
 
@@ -116,12 +124,7 @@ fn get_previous_word_start(cursor_position: Vec2<u16>, points: ShapePositions) -
 
     let jump_to = points.iter().enumerate().find(|(i, (position, shape_id))| {
         Ordering::is_lt(position.cmp(&cursor_position))
-            && shape_id.map_or(false, |id| {
-                points
-                    .get(i + 1)
-                    .and_then(|(_, id)| *id)
-                    .map_or(true, |n| n != id)
-            })
+            && shape_id.is_some_and(|id| points.get(i + 1).and_then(|(_, id)| *id) != Some(id))
     });
 
     jump_to.map(|(_, (point, _))| point.clone())
@@ -133,12 +136,7 @@ fn get_next_word_end(cursor_position: Vec2<u16>, points: ShapePositions) -> Jump
 
     let jump_to = points.iter().enumerate().find(|(i, (position, shape_id))| {
         Ordering::is_gt(position.cmp(&cursor_position))
-            && shape_id.map_or(false, |id| {
-                points
-                    .get(i + 1)
-                    .and_then(|(_, id)| *id)
-                    .map_or(true, |n| n != id)
-            })
+            && shape_id.is_some_and(|id| points.get(i + 1).and_then(|(_, id)| *id) != Some(id))
     });
 
     jump_to.map(|(_, (point, _))| point.clone())
@@ -1,40 +1,341 @@
+use std::{
+    fs::File,
+    io::{ErrorKind, Write},
+    path::Path,
+};
+
 use serde::{Deserialize, Serialize};
-use serde_json::Error;
+use serde_json::{json, Error, Value};
 
-use crate::state::State;
+use crate::{document::Document, history::History};
 
-#[derive(Serialize, Deserialize)]
-struct TrawFile {
-    version: FileVersion,
-    data: String,
+/// Every version of the file format, files are upgraded from older versions when they are loaded
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum FileVersion {
+    /// The drawing as a JSON string, inside of a JSON object with the version
+    V1,
+    /// The document as nested JSON, next to the version, with one shape per line
+    V2,
 }
 
-const CURRENT_VERSION: FileVersion = FileVersion::V1;
+const CURRENT_VERSION: FileVersion = FileVersion::V2;
 
-impl TrawFile {
-    pub fn new(data: String) -> Self {
-        TrawFile {
-            version: CURRENT_VERSION,
-            data,
-        }
+pub fn save(document: &Document, file_name: &str) -> std::io::Result<()> {
+    write_atomic(file_name, &to_file_string(document)?)
+}
+
+/// Write to a temporary file next to the real one and then move it into place, so that the file
+/// is never left half written if writing fails part of the way through
+fn write_atomic(file_name: &str, contents: &str) -> std::io::Result<()> {
+    let temporary = format!("{file_name}.tmp");
+    let result = write_temporary(&temporary, contents, file_name)
+        .and_then(|_| std::fs::rename(&temporary, file_name));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Get the contents all the way onto the disk before the file is moved into place, otherwise a
+/// crash can keep the move but lose the contents. The file gets the permissions of the one it
+/// replaces, so that saving does not change them
+fn write_temporary(path: &str, contents: &str, replacing: &str) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents.as_bytes())?;
+    if let Ok(metadata) = std::fs::metadata(replacing) {
+        file.set_permissions(metadata.permissions())?;
     }
+    file.sync_all()
+}
+
+/// Write out the document in the current version of the file format. Fields are always in the
+/// same order, and every shape is on a line of its own, so that changes to a drawing show up as
+/// small diffs
+fn to_file_string(document: &Document) -> Result<String, Error> {
+    let shapes = document
+        .shapes
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    let shapes = match shapes.is_empty() {
+        true => "[]".to_string(),
+        false => format!("[\n    {}\n  ]", shapes.join(",\n    ")),
+    };
+    Ok(format!(
+        "{{\n  \"version\": {},\n  \"metadata\": {},\n  \"canvas\": {},\n  \"shapes\": {}\n}}\n",
+        serde_json::to_string(&CURRENT_VERSION)?,
+        serde_json::to_string(&document.metadata)?,
+        serde_json::to_string(&document.canvas)?,
+        shapes
+    ))
+}
+
+/// Recovery file next to the drawing, hidden like vim's, e.g. `.drawing.traw.swp` for
+/// `drawing.traw`
+pub fn swap_path(file_name: &str) -> String {
+    let path = Path::new(file_name);
+    let name = path
+        .file_name()
+        .map_or(file_name.into(), |n| n.to_string_lossy());
+    path.with_file_name(format!(".{name}.swp"))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Write unsaved changes to the recovery file, so that they can be got back if traw does not
+/// exit cleanly
+pub fn save_swap(document: &Document, file_name: &str) -> std::io::Result<()> {
+    write_atomic(&swap_path(file_name), &to_file_string(document)?)
 }
 
+/// The document from the recovery file, if there is one that was written after the drawing was
+/// last saved
+pub fn load_swap(file_name: &str) -> Option<Document> {
+    let swap_path = swap_path(file_name);
+    let swapped = std::fs::metadata(&swap_path)
+        .and_then(|m| m.modified())
+        .ok()?;
+    let saved = std::fs::metadata(file_name).and_then(|m| m.modified());
+    if saved.is_ok_and(|saved| saved >= swapped) {
+        return None;
+    }
+    load(&swap_path).ok()
+}
+
+pub fn remove_swap(file_name: &str) {
+    let _ = std::fs::remove_file(swap_path(file_name));
+}
+
+/// Undo history kept in a file next to the drawing. It only applies to the drawing file as it was
+/// when the history was saved, which is checked with a stamp of its contents
 #[derive(Serialize, Deserialize)]
-enum FileVersion {
-    V1,
+struct HistoryFile {
+    drawing_stamp: u64,
+    history: History,
+}
+
+fn history_path(file_name: &str) -> String {
+    format!("{file_name}.undo")
+}
+
+/// FNV-1a hash of a drawing file, which gives the same stamp for the same contents in every build
+/// of traw unlike the hasher in the standard library
+fn stamp(contents: &[u8]) -> u64 {
+    contents.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Save the history next to the drawing, which has to have been saved first
+pub fn save_history(history: &History, file_name: &str) -> std::io::Result<()> {
+    let history_file = HistoryFile {
+        drawing_stamp: stamp(&std::fs::read(file_name)?),
+        history: history.clone(),
+    };
+    write_atomic(
+        &history_path(file_name),
+        &serde_json::to_string(&history_file)?,
+    )
+}
+
+/// The undo history saved for a drawing, if there is one and the drawing has not been changed
+/// without it since
+pub fn load_history(file_name: &str) -> Option<History> {
+    let data = std::fs::read(history_path(file_name)).ok()?;
+    let history_file: HistoryFile = serde_json::from_slice(&data).ok()?;
+    let drawing = std::fs::read(file_name).ok()?;
+    (history_file.drawing_stamp == stamp(&drawing)).then_some(history_file.history)
 }
 
-pub fn save(state: &State, file_name: &str) -> Result<(), Error> {
-    let data = serde_json::to_string(state)?;
-    let traw_file = TrawFile::new(data);
-    let _ = std::fs::write(file_name, serde_json::to_string(&traw_file)?);
-    Ok(())
+pub fn load(path: &str) -> std::io::Result<Document> {
+    let file: Value = serde_json::from_slice(std::fs::read(path)?.as_slice())?;
+    Ok(serde_json::from_value(migrate(file)?)?)
 }
 
-pub fn load(path: &str) -> std::io::Result<State> {
-    let traw_file: TrawFile = serde_json::from_slice(std::fs::read(path)?.as_slice())?;
-    match traw_file.version {
-        FileVersion::V1 => Ok(serde_json::from_str(&traw_file.data)?),
+/// Upgrade a file one version at a time until it is in the current version
+fn migrate(mut file: Value) -> std::io::Result<Value> {
+    loop {
+        let version = file.get("version").cloned().unwrap_or(Value::Null);
+        let Ok(version) = serde_json::from_value::<FileVersion>(version.clone()) else {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Unknown file version {version}, the file may have been saved by a newer \
+                    version of traw"
+                ),
+            ));
+        };
+        file = match version {
+            FileVersion::V1 => v1_to_v2(&file)?,
+            FileVersion::V2 => return Ok(file),
+        };
+    }
+}
+
+/// Version 1 files held the whole editor state as a string, which has the shapes in the same
+/// form as a document. Where the canvas was scrolled to is kept, the rest of it is left behind
+fn v1_to_v2(file: &Value) -> std::io::Result<Value> {
+    let data = file.get("data").and_then(Value::as_str).unwrap_or("{}");
+    let state: Value = serde_json::from_str(data)?;
+    let offset = state.pointer("/viewport/offset").cloned();
+    Ok(json!({
+        "version": FileVersion::V2,
+        "metadata": {},
+        "canvas": offset.map_or(json!({}), |offset| json!({ "offset": offset })),
+        "shapes": state.get("shapes").cloned().unwrap_or(json!([])),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{os::unix::fs::PermissionsExt, path::PathBuf};
+
+    use crate::{
+        components::rectangle::Rectangle,
+        document::Document,
+        mutate::Mutate,
+        shape::Shape,
+        state::{State, StateChange},
+        util::Vec2,
+    };
+
+    use super::{
+        load, load_history, load_swap, remove_swap, save, save_history, save_swap, swap_path,
+        to_file_string,
+    };
+
+    fn write_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn should_upgrade_version_1_files() {
+        let data = r#"{"shapes":[{"Rectangle":{"origin":{"x":1,"y":2},"width":3,"height":3,"text":["h","i"],"shape_id":7}}],"mode":"Normal","debug_enabled":true,"viewport":{"offset":{"x":4,"y":5}},"undo_stack":[],"redo_stack":[]}"#;
+        let file = serde_json::json!({ "version": "V1", "data": data });
+        let path = write_file("traw_should_upgrade_v1.traw", &file.to_string());
+
+        let document = load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let [Shape::Rectangle(rect)] = document.shapes.as_slice() else {
+            panic!("Expected a rectangle");
+        };
+        assert_eq!((rect.shape_id, rect.text.clone()), (7, vec!['h', 'i']));
+        assert_eq!(document.canvas.offset, Vec2::new(4, 5));
+    }
+
+    #[test]
+    fn should_save_one_shape_per_line() {
+        let mut document = Document::default();
+        let mut rect = Rectangle::new_at(0, 0);
+        rect.text = "hi".chars().collect();
+        document.shapes.push(Shape::Rectangle(rect));
+        document
+            .shapes
+            .push(Shape::Rectangle(Rectangle::new_at(5, 0)));
+
+        let contents = to_file_string(&document).unwrap();
+        let shape_lines = contents.lines().filter(|l| l.contains("Rectangle")).count();
+        assert_eq!(shape_lines, 2);
+        assert!(contents.contains(r#""text":"hi""#));
+
+        let path = write_file("traw_should_save_one_shape_per_line.traw", &contents);
+        let loaded = load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(loaded == document);
+    }
+
+    #[test]
+    fn should_explain_unknown_versions() {
+        let path = write_file("traw_should_explain_unknown.traw", r#"{"version":"V9"}"#);
+        let result = load(path.to_str().unwrap());
+        std::fs::remove_file(path).unwrap();
+        let Err(error) = result else {
+            panic!("Expected an error");
+        };
+        assert!(error.to_string().contains("newer version of traw"));
+    }
+
+    #[test]
+    fn should_report_failed_save_and_leave_no_temporary_file() {
+        let directory = std::env::temp_dir().join("traw_missing_directory");
+        let path = directory.join("drawing.traw");
+        assert!(save(&Document::default(), path.to_str().unwrap()).is_err());
+        assert!(!directory.exists());
+
+        let path = std::env::temp_dir().join("traw_should_save_atomically.traw");
+        save(&Document::default(), path.to_str().unwrap()).unwrap();
+        let permissions = std::fs::Permissions::from_mode(0o600);
+        std::fs::set_permissions(&path, permissions).unwrap();
+        save(&Document::default(), path.to_str().unwrap()).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let temporary = format!("{}.tmp", path.to_str().unwrap());
+        assert!(!std::path::Path::new(&temporary).exists());
+        assert!(load(path.to_str().unwrap()).unwrap().shapes.is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_only_recover_swap_newer_than_drawing() {
+        let path = std::env::temp_dir().join("traw_should_recover_swap.traw");
+        let path = path.to_str().unwrap();
+        assert!(swap_path(path).ends_with("/.traw_should_recover_swap.traw.swp"));
+
+        let mut document = Document::default();
+        save(&document, path).unwrap();
+        document
+            .shapes
+            .push(Shape::Rectangle(Rectangle::new_at(1, 1)));
+        save_swap(&document, path).unwrap();
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert_eq!(load_swap(path).unwrap().shapes.len(), 1);
+
+        // Saving afterwards makes the recovery file out of date
+        save(&document, path).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(swap_path(path))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert!(load_swap(path).is_none());
+
+        remove_swap(path);
+        assert!(!std::path::Path::new(&swap_path(path)).exists());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_only_load_history_for_unchanged_drawing() {
+        let path = std::env::temp_dir().join("traw_should_load_history.traw");
+        let path = path.to_str().unwrap();
+        let mut state = State::init();
+        for x in [1, 5] {
+            let undo = state.mutate(StateChange::AddShape(Shape::Rectangle(Rectangle::new_at(
+                x, 1,
+            ))));
+            state.history.record(undo);
+        }
+        save(&state.document, path).unwrap();
+        save_history(&state.history, path).unwrap();
+
+        // Changes are to shapes by id, so undoing with the loaded history removes the right ones
+        let mut loaded = State::open(load(path).unwrap());
+        loaded.open_history(load_history(path).unwrap());
+        loaded.undo();
+        assert!(loaded.document.shapes == state.document.shapes[..1]);
+
+        save(&Document::default(), path).unwrap();
+        assert!(load_history(path).is_none());
+        std::fs::remove_file(format!("{path}.undo")).unwrap();
+        std::fs::remove_file(path).unwrap();
     }
 }
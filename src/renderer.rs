@@ -4,23 +4,33 @@ use crossterm::{
     queue,
     style::{Print, SetBackgroundColor, SetForegroundColor},
 };
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
     cursor::{restore_position, save_position, set_position},
     draw::{Color, DrawOverlay, OverlayPoint, Point},
+    util::Vec2,
 };
 
 pub struct Renderer {
     pub state: Vec<Vec<Cell>>,
     prev_state: Vec<Vec<Cell>>,
+    /// Canvas position drawn in the top left cell of the screen
+    offset: Vec2<i32>,
     width: u16,
     height: u16,
     is_first_frame: bool,
 }
 
+/// Put in the cell to the right of a character that is two cells wide, nothing is printed there
+/// because the terminal fills it with the wide character
+const WIDE_SPACER: char = '\0';
+
 #[derive(PartialEq, Clone, Debug)]
 pub struct Cell {
     pub character: char,
+    /// Characters that combine with `character` into a single grapheme, like accents
+    pub marks: Vec<char>,
     pub shape_id: Option<u32>,
     foreground: Color,
     background: Color,
@@ -31,16 +41,28 @@ impl Renderer {
         Self {
             state: vec![],
             prev_state: vec![],
+            offset: Vec2::new(0, 0),
             width,
             height,
             is_first_frame: true,
         }
     }
 
-    pub fn render_frame<F>(&mut self, mut cb: F) -> std::io::Result<()>
+    /// Match a new terminal size. The next frame is drawn in full, as nothing on screen can be
+    /// relied on after the terminal has reflowed it
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.width = width;
+        self.height = height;
+        self.state = vec![];
+        self.prev_state = vec![];
+        self.is_first_frame = true;
+    }
+
+    pub fn render_frame<F>(&mut self, offset: &Vec2<i32>, mut cb: F) -> std::io::Result<()>
     where
         F: FnMut(&mut Self) -> std::io::Result<()>,
     {
+        self.offset = offset.clone();
         self.start_frame();
         cb(self)?;
         self.finish_frame()?;
@@ -51,6 +73,29 @@ impl Renderer {
         Ok(())
     }
 
+    /// Draw a frame into the cells without printing anything, for exporting the canvas instead of
+    /// showing it
+    pub fn render_offscreen<F>(&mut self, offset: &Vec2<i32>, mut cb: F) -> std::io::Result<()>
+    where
+        F: FnMut(&mut Self) -> std::io::Result<()>,
+    {
+        self.offset = offset.clone();
+        self.start_frame();
+        cb(self)
+    }
+
+    /// Every row of cells as the text that would be printed for it, without trailing whitespace
+    pub fn lines(&self) -> Vec<String> {
+        (0..self.height as usize)
+            .map(|y| {
+                let line: String = (0..self.width as usize)
+                    .filter_map(|x| printed(&self.state, x, y))
+                    .collect();
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+
     pub fn start_frame(&mut self) {
         let mut empty = vec![];
         for _ in 0..self.width {
@@ -58,6 +103,7 @@ impl Renderer {
             for _ in 0..self.height {
                 cols.push(Cell {
                     character: ' ',
+                    marks: vec![],
                     foreground: Color::Empty,
                     background: Color::EmptyBackground,
                     shape_id: None,
@@ -78,34 +124,88 @@ impl Renderer {
         points: Vec<Point<i32>>,
         shape_id: Option<u32>,
     ) -> std::io::Result<()> {
-        for point in points {
-            self.draw_at(point, shape_id)?;
-        }
-        Ok(())
+        let offset = self.offset.clone();
+        self.draw_points(
+            points.into_iter().map(|mut point| {
+                point.origin = point.origin - offset.clone();
+                point
+            }),
+            shape_id,
+        )
     }
 
     pub fn render_sticky(&mut self, points: Vec<Point<u16>>) -> std::io::Result<()> {
+        self.draw_points(points.into_iter().map(Point::into), None)
+    }
+
+    fn draw_points(
+        &mut self,
+        points: impl Iterator<Item = Point<i32>>,
+        shape_id: Option<u32>,
+    ) -> std::io::Result<()> {
+        let mut last = None;
         for point in points {
-            self.draw_at(point.into(), None)?;
+            if last.as_ref() == Some(&point.origin) {
+                self.combine_at(point)?;
+            } else {
+                last = Some(point.origin.clone());
+                self.draw_at(point, shape_id)?;
+            }
         }
         Ok(())
     }
 
+    /// Draw a point at a screen position, anything outside of the screen is clipped
     fn draw_at(&mut self, point: Point<i32>, shape_id: Option<u32>) -> std::io::Result<()> {
-        self.state[point.x as usize][point.y as usize] = Cell {
-            character: point.character,
-            foreground: point.foreground,
-            background: point.background,
-            shape_id,
-        };
+        if let Some(cell) = self.screen_cell_mut(point.x, point.y) {
+            *cell = Cell {
+                character: point.character,
+                marks: vec![],
+                foreground: point.foreground,
+                background: point.background,
+                shape_id,
+            };
+        }
+        self.reserve_wide(point.x, point.y);
 
         Ok(())
     }
 
+    /// Add a point drawn at the same position as the one before it to that cell, so that a
+    /// grapheme made of several characters stays together
+    fn combine_at(&mut self, point: Point<i32>) -> std::io::Result<()> {
+        if let Some(cell) = self.screen_cell_mut(point.x, point.y) {
+            cell.marks.push(point.character);
+        }
+        self.reserve_wide(point.x, point.y);
+
+        Ok(())
+    }
+
+    /// Cover the cell to the right of a wide character with a spacer
+    fn reserve_wide(&mut self, x: i32, y: i32) {
+        let Some(cell) = self.screen_cell_mut(x, y) else {
+            return;
+        };
+        if cell.width() < 2 {
+            return;
+        }
+        let spacer = Cell {
+            character: WIDE_SPACER,
+            marks: vec![],
+            ..cell.clone()
+        };
+        if let Some(next) = self.screen_cell_mut(x + 1, y) {
+            *next = spacer;
+        }
+    }
+
     pub fn render_overlay(&mut self, overlay: &impl DrawOverlay) -> std::io::Result<()> {
         let (points, foreground, background) = overlay.draw_overlay()?;
         for OverlayPoint { x, y } in points {
-            let point = &mut self.state[x as usize][y as usize];
+            let Some(point) = self.screen_cell_mut(x - self.offset.x, y - self.offset.y) else {
+                continue;
+            };
             if let Some(fg) = foreground {
                 point.foreground = fg;
             }
@@ -116,6 +216,27 @@ impl Renderer {
         Ok(())
     }
 
+    /// The cell rendered at a canvas position, if it is currently on screen
+    pub fn get_cell(&self, position: &Vec2<i32>) -> Option<&Cell> {
+        let x = position.x - self.offset.x;
+        let y = position.y - self.offset.y;
+        if x < 0 || y < 0 {
+            return None;
+        }
+        self.state
+            .get(x as usize)
+            .and_then(|col| col.get(y as usize))
+    }
+
+    fn screen_cell_mut(&mut self, x: i32, y: i32) -> Option<&mut Cell> {
+        if x < 0 || y < 0 {
+            return None;
+        }
+        self.state
+            .get_mut(x as usize)
+            .and_then(|col| col.get_mut(y as usize))
+    }
+
     pub fn finish_frame(&self) -> std::io::Result<()> {
         save_position();
         self.state
@@ -125,14 +246,19 @@ impl Renderer {
                 row.iter()
                     .enumerate()
                     .map(|(y, point)| {
-                        let prev = &self.prev_state[x][y];
-                        if point != prev || self.is_first_frame {
+                        let text = printed(&self.state, x, y);
+                        let changed = point != &self.prev_state[x][y]
+                            || text != printed(&self.prev_state, x, y);
+                        let Some(text) = text else {
+                            return Ok(());
+                        };
+                        if changed || self.is_first_frame {
                             set_position((x as u16, y as u16).into());
                             queue!(
                                 stdout(),
                                 SetForegroundColor(point.foreground.into()),
                                 SetBackgroundColor(point.background.into()),
-                                Print(point.character)
+                                Print(text)
                             )?;
                         }
                         std::io::Result::Ok(())
@@ -144,3 +270,100 @@ impl Renderer {
         Ok(())
     }
 }
+
+impl Cell {
+    /// The whole grapheme in the cell, empty for the right half of a wide character
+    pub fn text(&self) -> String {
+        if self.is_spacer() {
+            return String::new();
+        }
+        std::iter::once(self.character)
+            .chain(self.marks.iter().copied())
+            .collect()
+    }
+
+    pub fn is_spacer(&self) -> bool {
+        self.character == WIDE_SPACER
+    }
+
+    fn width(&self) -> usize {
+        self.text().width()
+    }
+}
+
+/// What to print for a cell, nothing for the right half of a wide character. When one half of a
+/// wide character has been drawn over the other half is left blank, so that the rest of the line
+/// is not pushed out of place
+fn printed(state: &[Vec<Cell>], x: usize, y: usize) -> Option<String> {
+    let cell = &state[x][y];
+    if cell.is_spacer() {
+        let covered = x
+            .checked_sub(1)
+            .is_some_and(|prev| state[prev][y].width() == 2);
+        return (!covered).then(|| " ".to_string());
+    }
+    let reserved = state.get(x + 1).is_some_and(|next| next[y].is_spacer());
+    if cell.width() == 2 && !reserved {
+        return Some(" ".to_string());
+    }
+    Some(cell.text())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        draw::{Color, Point},
+        util::Vec2,
+    };
+
+    use super::{printed, Renderer};
+
+    fn point(x: i32, character: char) -> Point<i32> {
+        Point {
+            origin: Vec2::new(x, 0),
+            character,
+            foreground: Color::Border,
+            background: Color::BorderBackground,
+        }
+    }
+
+    #[test]
+    fn should_keep_graphemes_and_wide_characters_together() {
+        let mut renderer = Renderer::new(4, 1);
+        renderer.start_frame();
+        renderer
+            .render(
+                vec![point(0, '日'), point(2, 'e'), point(2, '\u{301}')],
+                None,
+            )
+            .unwrap();
+        let row = |r: &Renderer| (0..4).map(|x| printed(&r.state, x, 0)).collect::<Vec<_>>();
+        assert_eq!(
+            row(&renderer),
+            vec![
+                Some("日".to_string()),
+                None,
+                Some("e\u{301}".to_string()),
+                Some(" ".to_string())
+            ]
+        );
+
+        // Drawing over half of a wide character blanks the other half
+        renderer.render(vec![point(1, 'x')], None).unwrap();
+        assert_eq!(printed(&renderer.state, 0, 0), Some(" ".to_string()));
+        assert_eq!(printed(&renderer.state, 1, 0), Some("x".to_string()));
+    }
+
+    #[test]
+    fn should_match_new_size_after_resize() {
+        let mut renderer = Renderer::new(4, 2);
+        renderer.start_frame();
+        renderer.start_frame();
+        renderer.resize(2, 3);
+        renderer.start_frame();
+        assert_eq!((renderer.state.len(), renderer.state[0].len()), (2, 3));
+        assert_eq!(renderer.prev_state.len(), 2);
+        assert!(renderer.is_first_frame);
+        renderer.render(vec![point(3, 'x')], None).unwrap();
+    }
+}
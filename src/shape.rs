@@ -7,12 +7,35 @@ use crate::{
     util::Vec2,
 };
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub enum Shape {
     Rectangle(Rectangle),
     Arrow(Arrow),
 }
 
+impl Shape {
+    pub fn shape_id(&self) -> u32 {
+        match self {
+            Shape::Arrow(shape) => shape.shape_id,
+            Shape::Rectangle(shape) => shape.shape_id,
+        }
+    }
+}
+
+/// Used for moving an object around the canvas
+pub trait Translate {
+    fn translate(&mut self, delta: &Vec2<i32>);
+}
+
+impl Translate for Shape {
+    fn translate(&mut self, delta: &Vec2<i32>) {
+        match self {
+            Shape::Arrow(shape) => shape.translate(delta),
+            Shape::Rectangle(shape) => shape.translate(delta),
+        }
+    }
+}
+
 impl Draw for Shape {
     fn draw(&self) -> std::io::Result<Vec<Point<i32>>> {
         match self {
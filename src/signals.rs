@@ -0,0 +1,31 @@
+use signal_hook::{
+    consts::{SIGHUP, SIGINT, SIGTERM, SIGTSTP},
+    iterator::Signals,
+    low_level,
+};
+
+/// Signals that would end traw straight away, which are caught instead so that it can restore
+/// the terminal and save the drawing before exiting
+const STOPPING: [i32; 3] = [SIGTERM, SIGHUP, SIGINT];
+
+pub struct StopSignals(Signals);
+
+impl StopSignals {
+    pub fn register() -> std::io::Result<Self> {
+        Ok(Self(Signals::new(STOPPING)?))
+    }
+
+    /// A stopping signal that has been received since the last time this was checked
+    pub fn received(&mut self) -> Option<i32> {
+        self.0.pending().next()
+    }
+}
+
+pub fn name(signal: i32) -> &'static str {
+    low_level::signal_name(signal).unwrap_or("a signal")
+}
+
+/// Stop the process the way Ctrl-Z does outside of raw mode, returning once it is resumed
+pub fn suspend() -> std::io::Result<()> {
+    low_level::raise(SIGTSTP)
+}
@@ -1,48 +1,235 @@
-use std::io::{stdout, Result};
+use std::{
+    io::{stdout, Result},
+    time::{Duration, Instant},
+};
 
 use crossterm::{
     cursor::{self},
+    event::KeyEvent,
     queue,
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    components::{arrow::Arrow, debug_panel::debug, rectangle::Rectangle},
-    cursor::{cursor_position, set_position},
+    command::{Command, HistoryStep},
+    components::{
+        arrow::{Arrow, ArrowHandle, Label},
+        debug_panel::debug,
+        rectangle::{Drag, Rectangle},
+        status_bar::Message,
+    },
+    document::Document,
     draw::{
         Color, CursorIntersect, Draw, DrawOverlay,
         EdgeIntersection::{Corner, Side},
         Intersection, OverlayPoint,
     },
-    mode::{Anchor, Mode, Selection},
+    export::{self, Format},
+    history::History,
+    mode::{Anchor, ArrowEdit, Grab, LabelEdit, Mode, Movement, Routing, Selection, TextEdit},
     mutate::Mutate,
-    shape::Shape,
+    persistence,
+    shape::{Shape, Translate},
     util::Vec2,
+    viewport::Viewport,
 };
 
-#[derive(Serialize, Deserialize)]
+/// How often unsaved changes are written to the recovery file
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The editor, wrapping the document being edited with everything about the editing session that
+/// is not saved with it
 pub struct State {
-    pub shapes: Vec<Shape>,
+    pub document: Document,
     pub mode: Mode,
     pub debug_enabled: bool,
-    undo_stack: Vec<StateChange>,
-    redo_stack: Vec<StateChange>,
+    pub viewport: Viewport,
+    /// Saved to a file of its own when that is turned on
+    pub history: History,
+    /// Where the drawing is saved, and whether the history is saved alongside it
+    pub file_name: String,
+    pub undofile: bool,
+    /// Result of the last action that has something to report, shown in the status bar
+    pub message: Option<Message>,
+    /// Set once the editor should exit
+    pub should_quit: bool,
+    /// State of the history when the drawing was last saved or opened, none when the drawing was
+    /// recovered and has not been saved since
+    saved_at: Option<usize>,
+    /// What was last written to the recovery file, and when
+    autosaved: Option<Document>,
+    autosaved_time: Instant,
+    /// First key of a two key command in normal mode, e.g. the 'g' of 'g-'
+    pending: Option<char>,
+    /// Arrows are re-routed while the boxes they are attached to are being edited, the original
+    /// versions are kept here so that the whole edit can be undone in one step
+    snapshots: Vec<Shape>,
+    /// Existing shape taken out of the list of shapes to be edited, and where it was, so that it
+    /// can be put back if the edit is cancelled
+    original: Option<(usize, Shape)>,
 }
 
 impl State {
+    /// Start editing a document, showing the part of the canvas it was last saved at
+    pub fn open(document: Document) -> Self {
+        let mut state = Self::init();
+        state.viewport.offset = document.canvas.offset.clone();
+        state.document = document;
+        state
+    }
+
+    /// The document as it should be saved, with the canvas settings brought up to date with the
+    /// editor
+    pub fn document_to_save(&mut self) -> &Document {
+        self.document.canvas.offset = self.viewport.offset.clone();
+        self.document.metadata.saved_with = Some(env!("CARGO_PKG_VERSION").to_string());
+        &self.document
+    }
+
+    /// Keep the history from an earlier session, the drawing being saved as it is at the end of it
+    pub fn open_history(&mut self, history: History) {
+        self.saved_at = Some(history.current());
+        self.history = history;
+    }
+
+    /// Carry on from a document got back from the recovery file, which is unsaved and has none
+    /// of the undo history that led to it
+    pub fn recover(&mut self, document: Document) {
+        self.viewport.offset = document.canvas.offset.clone();
+        self.document = document;
+        self.history = History::default();
+        self.saved_at = None;
+    }
+
+    /// Whether there are changes that have not been saved
+    pub fn is_dirty(&self) -> bool {
+        self.saved_at != Some(self.history.current())
+    }
+
+    /// The document with everything done to it so far, including the shape that is being drawn
+    /// or edited, which is not in the list of shapes until it is finished
+    pub fn document_to_recover(&self) -> Document {
+        let mut document = self.document.clone();
+        document.canvas.offset = self.viewport.offset.clone();
+        let editing = match &self.mode {
+            Mode::Text(edit) => Some(Shape::Rectangle(edit.target.clone())),
+            Mode::DrawRectangle(rect, _) => Some(Shape::Rectangle(rect.clone())),
+            Mode::DrawArrow(arrow) => Some(Shape::Arrow(arrow.clone())),
+            _ => None,
+        };
+        let (i, shape) = match (&self.original, editing) {
+            (Some((i, _)), Some(shape)) => (*i, shape),
+            (Some((i, original)), None) => (*i, original.clone()),
+            (None, Some(shape)) => (document.shapes.len(), shape),
+            (None, None) => return document,
+        };
+        document.shapes.insert(i.min(document.shapes.len()), shape);
+        document
+    }
+
+    /// Write the changes to the recovery file if they have not been saved and it has been long
+    /// enough since it was last written, returns whether it was written
+    pub fn autosave(&mut self) -> bool {
+        if self.autosaved_time.elapsed() < AUTOSAVE_INTERVAL || !self.has_unsaved_work() {
+            return false;
+        }
+        let document = self.document_to_recover();
+        if self.autosaved.as_ref() == Some(&document) {
+            return false;
+        }
+        if let Err(e) = persistence::save_swap(&document, &self.file_name) {
+            self.message = Some(Message::error(format!(
+                "Could not write recovery file: {e}"
+            )));
+        }
+        self.autosaved = Some(document);
+        self.autosaved_time = Instant::now();
+        true
+    }
+
+    /// Last chance to keep unsaved changes when traw is exiting without being able to save
+    /// normally, writes them to the recovery file to be recovered when the drawing is next opened.
+    /// Returns where they were written, if there were any
+    pub fn save_for_recovery(&self) -> Option<std::io::Result<String>> {
+        if !self.has_unsaved_work() {
+            return None;
+        }
+        let result = persistence::save_swap(&self.document_to_recover(), &self.file_name);
+        Some(result.map(|_| persistence::swap_path(&self.file_name)))
+    }
+
+    /// Whether there are changes that have not been saved, or shapes are in the middle of being
+    /// drawn or edited
+    fn has_unsaved_work(&self) -> bool {
+        self.is_dirty() || !matches!(self.mode, Mode::Normal | Mode::Select(_) | Mode::Command(_))
+    }
+
+    /// Save the drawing, and its history if that is turned on, reporting how it went in the
+    /// status bar
+    pub fn save(&mut self) -> bool {
+        let file_name = self.file_name.clone();
+        let mut result = persistence::save(self.document_to_save(), &file_name);
+        if self.undofile {
+            result = result.and_then(|_| persistence::save_history(&self.history, &file_name));
+        }
+        match result {
+            Ok(_) => {
+                self.saved_at = Some(self.history.current());
+                self.message = Some(Message::info(format!("Saved {file_name}")));
+                true
+            }
+            Err(e) => {
+                self.message = Some(Message::error(format!("Could not save {file_name}: {e}")));
+                false
+            }
+        }
+    }
+
+    /// Write the drawing in another format next to the drawing file
+    fn export(&mut self, format: &Format) {
+        let path = export::export_path(&self.file_name, format);
+        let result = export::export(self.document_to_recover(), format)
+            .and_then(|output| std::fs::write(&path, output));
+        self.message = Some(match result {
+            Ok(_) => Message::info(format!("Exported {path}")),
+            Err(e) => Message::error(format!("Could not export {path}: {e}")),
+        });
+    }
+
+    /// Exit, unless there are unsaved changes and it is not forced
+    pub fn quit(&mut self, force: bool) {
+        if self.is_dirty() && !force {
+            self.message = Some(Message::error(
+                "There are unsaved changes, save with :w or quit anyway with :q!".to_string(),
+            ));
+            return;
+        }
+        self.should_quit = true;
+    }
+
     pub fn init() -> Self {
         Self {
-            shapes: vec![],
+            document: Document::default(),
             mode: Mode::Normal,
             debug_enabled: false,
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
+            viewport: Viewport::default(),
+            history: History::default(),
+            file_name: "unnamed.traw".to_string(),
+            undofile: false,
+            message: None,
+            should_quit: false,
+            saved_at: Some(0),
+            autosaved: None,
+            autosaved_time: Instant::now(),
+            pending: None,
+            snapshots: Vec::new(),
+            original: None,
         }
     }
 
     pub fn handle_insert(&mut self) -> std::io::Result<()> {
         if let Mode::Normal = &self.mode {
-            let position: Vec2<i32> = cursor_position().into();
+            let position = self.viewport.cursor_position();
             let (intersection, i) = self.get_cursor_intersection()?;
 
             match intersection {
@@ -52,11 +239,20 @@ impl State {
                         Anchor::BottomRight,
                     ));
                 }
-                Intersection::Edge(Side) => {
-                    self.enter_mode(Mode::DrawArrow(Arrow::init()));
-                }
+                Intersection::Edge(Side) => match &self.document.shapes[i] {
+                    Shape::Arrow(arrow) => {
+                        self.enter_mode(Mode::EditArrow(ArrowEdit {
+                            shape_id: arrow.shape_id,
+                            grab: None,
+                        }));
+                    }
+                    Shape::Rectangle(_) => {
+                        self.enter_mode(Mode::DrawArrow(Arrow::init()));
+                    }
+                },
                 Intersection::Inner => {
-                    let edited = self.shapes.remove(i);
+                    let edited = self.document.shapes.remove(i);
+                    self.original = Some((i, edited.clone()));
                     match edited {
                         Shape::Rectangle(rectangle) => {
                             self.enter_text_mode(rectangle)?;
@@ -77,7 +273,8 @@ impl State {
         if let Mode::Normal = self.mode {
             let (intersection, i) = self.get_cursor_intersection()?;
             if let Intersection::Edge(Corner(Some(anchor))) = intersection {
-                if let Shape::Rectangle(rectangle) = self.shapes.remove(i) {
+                if let Shape::Rectangle(rectangle) = self.document.shapes.remove(i) {
+                    self.original = Some((i, Shape::Rectangle(rectangle.clone())));
                     self.enter_mode(Mode::DrawRectangle(rectangle, anchor))
                 }
             }
@@ -87,16 +284,241 @@ impl State {
     }
 
     pub fn handle_select(&mut self) -> std::io::Result<()> {
-        let position = cursor_position();
+        let position = self.viewport.cursor_position();
         self.enter_mode(Mode::Select(Selection {
-            x: position.x as i32,
-            y: position.y as i32,
+            x: position.x,
+            y: position.y,
             width: 1,
             height: 1,
         }));
         Ok(())
     }
 
+    /// Start moving the hovered shape, or every shape inside of the selection
+    pub fn handle_move(&mut self) -> std::io::Result<()> {
+        let shape_ids = match &self.mode {
+            Mode::Normal => match self.get_cursor_intersection()? {
+                (Intersection::None, _) => vec![],
+                (_, i) => vec![self.document.shapes[i].shape_id()],
+            },
+            Mode::Select(selection) => {
+                let mut shape_ids = vec![];
+                for shape in &self.document.shapes {
+                    if selection.contains_all(&shape.draw()?) {
+                        shape_ids.push(shape.shape_id());
+                    }
+                }
+                shape_ids
+            }
+            _ => vec![],
+        };
+
+        if !shape_ids.is_empty() {
+            // Moved arrows may also be re-routed if they are attached to a box that is not moving,
+            // so they need to be saved before they are translated
+            for shape in &self.document.shapes {
+                if let Shape::Arrow(_) = shape {
+                    if shape_ids.contains(&shape.shape_id()) {
+                        self.snapshots.push(shape.clone());
+                    }
+                }
+            }
+            let cursor = self.viewport.cursor_position();
+            self.enter_mode(Mode::Move(Movement::new(shape_ids, cursor)));
+        }
+
+        Ok(())
+    }
+
+    /// Start a routed arrow from the hovered box
+    pub fn handle_route(&mut self) -> std::io::Result<()> {
+        if let Mode::Normal = self.mode {
+            if let (Intersection::Inner | Intersection::Edge(_), i) =
+                self.get_cursor_intersection()?
+            {
+                if let Shape::Rectangle(rect) = &self.document.shapes[i] {
+                    self.enter_mode(Mode::Route(Routing {
+                        source: rect.shape_id,
+                        target: None,
+                        preview: None,
+                    }));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Commands for grabbing and changing the arrow being edited, returns false if the key is
+    /// not one of them
+    pub fn handle_arrow_edit(&mut self, key: char) -> std::io::Result<bool> {
+        let Mode::EditArrow(edit) = &mut self.mode else {
+            return Ok(false);
+        };
+        let shape_id = edit.shape_id;
+        let grab = edit.grab.take();
+        let Some(Shape::Arrow(arrow)) = self
+            .document
+            .shapes
+            .iter()
+            .find(|s| s.shape_id() == shape_id)
+            .cloned()
+        else {
+            return Ok(false);
+        };
+        let cursor = self.viewport.cursor_position();
+        let mut edited = arrow.clone();
+
+        match (key, grab) {
+            ('g', Some(grab)) => self.release_grab(shape_id, grab),
+            ('g', None) => {
+                let grab = arrow.handle_at(&cursor).map(|handle| Grab {
+                    handle,
+                    points: arrow.points.clone(),
+                    original: arrow,
+                    start: cursor,
+                });
+                self.set_grab(grab);
+            }
+            ('o', None) => {
+                if let Some(handle) = edited.insert_bend(&cursor) {
+                    self.set_grab(Some(Grab {
+                        handle,
+                        points: edited.points,
+                        original: arrow,
+                        start: cursor,
+                    }));
+                }
+            }
+            ('x', None) => {
+                if let Some(ArrowHandle::Corner(i)) = arrow.handle_at(&cursor) {
+                    edited.remove_bend(i);
+                    self.commit_arrow_edit(edited);
+                }
+            }
+            ('r', None) => {
+                edited.reverse();
+                self.commit_arrow_edit(edited);
+            }
+            ('t', None) => {
+                edited.label.segment = match arrow.handle_at(&cursor) {
+                    Some(ArrowHandle::Segment(i)) => Some(i),
+                    _ => None,
+                };
+                let label = edited.label.clone();
+                self.mutate(StateChange::ReplaceShape(Shape::Arrow(edited)));
+                self.enter_label_mode(arrow, label)?;
+            }
+            (_, grab) => {
+                self.set_grab(grab);
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn set_grab(&mut self, grab: Option<Grab>) {
+        if let Mode::EditArrow(edit) = &mut self.mode {
+            edit.grab = grab;
+        }
+    }
+
+    /// Finish dragging part of an arrow, recording the whole drag as one undo
+    fn release_grab(&mut self, shape_id: u32, grab: Grab) {
+        let dragged = self
+            .document
+            .shapes
+            .iter()
+            .find(|s| s.shape_id() == shape_id)
+            .cloned();
+        if let Some(Shape::Arrow(dragged)) = dragged {
+            if dragged.points == grab.original.points {
+                return;
+            }
+            self.mutate(StateChange::ReplaceShape(Shape::Arrow(grab.original)));
+            self.commit_arrow_edit(dragged);
+        }
+    }
+
+    fn commit_arrow_edit(&mut self, mut arrow: Arrow) {
+        // Edited arrows keep their shape instead of being routed automatically
+        arrow.routed = false;
+        arrow.bind(&rectangles(&self.document.shapes));
+        let undo = self.mutate(StateChange::ReplaceShape(Shape::Arrow(arrow)));
+        self.push_undo(undo);
+    }
+
+    /// Bring the shape being drawn, resized or moved up to date with the cursor
+    pub fn follow_cursor(&mut self) -> std::io::Result<()> {
+        let cursor = self.viewport.cursor_position();
+        match &mut self.mode {
+            Mode::DrawRectangle(rect, anchor) => {
+                rect.drag_corner(anchor, &cursor)?;
+                reattach_arrows(&mut self.document.shapes, &mut self.snapshots, rect);
+            }
+            Mode::DrawArrow(arrow) => {
+                arrow.update(cursor);
+            }
+            Mode::Select(selection) => {
+                selection.drag_corner(&mut Anchor::BottomRight, &cursor)?;
+            }
+            Mode::Move(movement) => {
+                let delta = movement.update(&cursor);
+                translate_shapes(&mut self.document.shapes, &movement.shape_ids, &delta);
+                let moved: Vec<Rectangle> = self
+                    .document
+                    .shapes
+                    .iter()
+                    .filter_map(|s| match s {
+                        Shape::Rectangle(r) if movement.shape_ids.contains(&r.shape_id) => {
+                            Some(r.clone())
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                for rect in &moved {
+                    reattach_arrows(&mut self.document.shapes, &mut self.snapshots, rect);
+                }
+            }
+            Mode::Route(routing) => {
+                let rectangles = rectangles(&self.document.shapes);
+                let source = rectangles.iter().find(|r| r.shape_id == routing.source);
+                let target = rectangles
+                    .iter()
+                    .find(|r| r.shape_id != routing.source && r.hovered(&cursor));
+                if routing.target != target.map(|r| r.shape_id) {
+                    routing.target = target.map(|r| r.shape_id);
+                    routing.preview = match (source, target) {
+                        (Some(source), Some(target)) => Arrow::route(source, target, &rectangles),
+                        _ => None,
+                    };
+                }
+            }
+            Mode::EditArrow(ArrowEdit {
+                shape_id,
+                grab: Some(grab),
+            }) => {
+                let arrow = self
+                    .document
+                    .shapes
+                    .iter_mut()
+                    .find(|s| s.shape_id() == *shape_id);
+                if let Some(Shape::Arrow(arrow)) = arrow {
+                    arrow.points = grab.points.clone();
+                    arrow.drag(&grab.handle, &(cursor - grab.start.clone()));
+                }
+            }
+            Mode::Normal
+            | Mode::Text(_)
+            | Mode::EditArrow(_)
+            | Mode::Label(_)
+            | Mode::Command(_) => {}
+        }
+
+        Ok(())
+    }
+
     pub fn handle_enter(&mut self) -> std::io::Result<()> {
         match std::mem::take(&mut self.mode) {
             Mode::DrawRectangle(rect, _) => {
@@ -104,36 +526,167 @@ impl State {
                 if rect.text.is_empty() {
                     self.enter_text_mode(rect)?;
                 } else {
-                    self.add_shape(Shape::Rectangle(rect));
+                    self.commit_shape(Shape::Rectangle(rect));
                     self.enter_mode(Mode::Normal);
                 }
             }
-            Mode::Text(rect) => {
-                self.add_shape(Shape::Rectangle(rect));
+            Mode::Text(edit) => {
+                self.commit_shape(Shape::Rectangle(edit.target));
                 queue!(stdout(), cursor::SetCursorStyle::SteadyBlock)?;
             }
-            Mode::DrawArrow(arrow) => {
-                self.add_shape(Shape::Arrow(arrow));
+            Mode::DrawArrow(mut arrow) => {
+                arrow.bind(&rectangles(&self.document.shapes));
+                self.commit_shape(Shape::Arrow(arrow));
+            }
+            Mode::Route(routing) => {
+                if let Some(arrow) = routing.preview {
+                    self.add_shape(Shape::Arrow(arrow));
+                }
             }
             Mode::Select(_) => {
                 self.enter_mode(Mode::Normal);
             }
+            Mode::EditArrow(edit) => {
+                if let Some(grab) = edit.grab {
+                    self.release_grab(edit.shape_id, grab);
+                }
+            }
+            Mode::Label(edit) => {
+                // The label has already been edited, so only the undo needs to be recorded
+                let edited = self
+                    .document
+                    .shapes
+                    .iter()
+                    .find(|s| s.shape_id() == edit.shape_id)
+                    .is_some_and(
+                        |s| matches!(s, Shape::Arrow(arrow) if arrow.label != edit.original.label),
+                    );
+                if edited {
+                    self.push_undo(StateChange::ReplaceShape(Shape::Arrow(edit.original)));
+                }
+                queue!(stdout(), cursor::SetCursorStyle::SteadyBlock)?;
+            }
+            Mode::Move(movement) => {
+                // Shapes have already been moved, so only the undo needs to be recorded
+                let undo = movement.start - movement.current;
+                if undo != Vec2::default() {
+                    self.push_undo(StateChange::MoveShapes(movement.shape_ids, undo));
+                } else {
+                    self.snapshots.clear();
+                }
+            }
+            Mode::Command(command) => match Command::parse(&command) {
+                Some(command) => self.run_command(command),
+                None => self.message = Some(Message::error(format!("Not a command: {command}"))),
+            },
             Mode::Normal => {}
         }
 
         Ok(())
     }
 
+    /// Leave the current mode without keeping what was done in it, see [`Mode`] for where each
+    /// mode goes
+    pub fn handle_escape(&mut self) -> std::io::Result<()> {
+        match std::mem::take(&mut self.mode) {
+            Mode::Text(_) => {
+                queue!(stdout(), cursor::SetCursorStyle::SteadyBlock)?;
+            }
+            Mode::Label(edit) => {
+                self.mutate(StateChange::ReplaceShape(Shape::Arrow(edit.original)));
+                queue!(stdout(), cursor::SetCursorStyle::SteadyBlock)?;
+            }
+            Mode::Move(movement) => {
+                let back = movement.start - movement.current;
+                translate_shapes(&mut self.document.shapes, &movement.shape_ids, &back);
+            }
+            Mode::EditArrow(ArrowEdit {
+                grab: Some(grab), ..
+            }) => {
+                self.mutate(StateChange::ReplaceShape(Shape::Arrow(grab.original)));
+            }
+            Mode::Normal
+            | Mode::DrawRectangle(..)
+            | Mode::DrawArrow(_)
+            | Mode::Select(_)
+            | Mode::Route(_)
+            | Mode::EditArrow(_)
+            | Mode::Command(_) => {}
+        }
+        self.restore_original();
+
+        Ok(())
+    }
+
+    /// Undo everything an edit has done to the shapes, without recording it. Arrows that were
+    /// re-routed go back to how they were and the shape being edited goes back where it was
+    fn restore_original(&mut self) {
+        for snapshot in std::mem::take(&mut self.snapshots) {
+            self.mutate(StateChange::ReplaceShape(snapshot));
+        }
+        if let Some((i, shape)) = self.original.take() {
+            self.document
+                .shapes
+                .insert(i.min(self.document.shapes.len()), shape);
+        }
+    }
+
     fn add_shape(&mut self, shape: Shape) {
         let mx = self.mutate(StateChange::AddShape(shape));
-        self.undo_stack.push(mx);
+        self.push_undo(mx);
+    }
+
+    /// Finish drawing a new shape or editing an existing one. An edited shape goes back where it
+    /// was in the list of shapes, so that it is drawn in the same order, and undoing restores the
+    /// exact shape from before the edit
+    fn commit_shape(&mut self, shape: Shape) {
+        let Some((i, original)) = self.original.take() else {
+            self.add_shape(shape);
+            return;
+        };
+        let unchanged = shape == original;
+        self.document
+            .shapes
+            .insert(i.min(self.document.shapes.len()), shape);
+        if !unchanged || !self.snapshots.is_empty() {
+            self.push_undo(StateChange::ReplaceShape(original));
+        }
+    }
+
+    /// Record an undo, along with restoring any arrows that were re-routed by the same edit. Any
+    /// changes that were undone are kept on a branch of the history of their own
+    fn push_undo(&mut self, mx: StateChange) {
+        if self.snapshots.is_empty() {
+            self.history.record(mx);
+        } else {
+            let mut changes = vec![mx];
+            changes.extend(self.snapshots.drain(..).map(StateChange::ReplaceShape));
+            self.history.record(StateChange::Batch(changes));
+        }
     }
 
     fn enter_text_mode(&mut self, rect: Rectangle) -> std::io::Result<()> {
         queue!(stdout(), cursor::SetCursorStyle::SteadyBar)?;
-        let next = rect.get_inner_cursor_position();
-        self.enter_mode(Mode::Text(rect));
-        set_position(next.into());
+        let edit = TextEdit::new(rect);
+        let next = edit.target.get_text_cursor_position(edit.cursor);
+        self.enter_mode(Mode::Text(edit));
+        self.viewport.set_cursor_position(next);
+
+        Ok(())
+    }
+
+    fn enter_label_mode(&mut self, original: Arrow, label: Label) -> std::io::Result<()> {
+        queue!(stdout(), cursor::SetCursorStyle::SteadyBar)?;
+        let edit = LabelEdit {
+            shape_id: original.shape_id,
+            original,
+            text: TextEdit::new(label),
+        };
+        let next = update_label(&mut self.document.shapes, &edit);
+        self.enter_mode(Mode::Label(edit));
+        if let Some(next) = next {
+            self.viewport.set_cursor_position(next);
+        }
 
         Ok(())
     }
@@ -148,8 +701,17 @@ impl State {
             let (intersection, i) = self.get_cursor_intersection()?;
             match intersection {
                 Intersection::Edge(_) | Intersection::Inner => {
-                    let mx = self.mutate(StateChange::DeleteShape(i));
-                    self.undo_stack.push(mx);
+                    let shape_id = self.document.shapes[i].shape_id();
+                    for shape in self.document.shapes.iter_mut() {
+                        if let Shape::Arrow(arrow) = shape {
+                            if arrow.is_bound_to(shape_id) {
+                                self.snapshots.push(Shape::Arrow(arrow.clone()));
+                                arrow.detach(shape_id);
+                            }
+                        }
+                    }
+                    let mx = self.mutate(StateChange::DeleteShape(shape_id));
+                    self.push_undo(mx);
                 }
                 _ => {}
             }
@@ -158,40 +720,180 @@ impl State {
         Ok(())
     }
 
+    /// Change the style of the hovered shape. For arrows this cycles the head position, head
+    /// glyphs or line weight, or toggles whether it hops over lines. For boxes it cycles the text
+    /// alignment or auto-size setting, changes the padding, or fits the box to its text
+    pub fn handle_restyle(&mut self, key: char) -> std::io::Result<()> {
+        if let Mode::Normal = self.mode {
+            let (intersection, i) = self.get_cursor_intersection()?;
+            if let Intersection::None = intersection {
+                return Ok(());
+            }
+            let mut shape = self.document.shapes[i].clone();
+            match (&mut shape, key) {
+                (Shape::Arrow(arrow), 'H') => arrow.style.head = arrow.style.head.next(),
+                (Shape::Arrow(arrow), 'G') => arrow.style.glyphs = arrow.style.glyphs.next(),
+                (Shape::Arrow(arrow), 'L') => arrow.style.line = arrow.style.line.next(),
+                (Shape::Arrow(arrow), 'B') => arrow.style.hop = !arrow.style.hop,
+                (Shape::Rectangle(rect), 'A') => {
+                    rect.text_style.align = rect.text_style.align.next()
+                }
+                (Shape::Rectangle(rect), 'V') => {
+                    rect.text_style.vertical_align = rect.text_style.vertical_align.next()
+                }
+                (Shape::Rectangle(rect), '>') => rect.text_style.padding += 1,
+                (Shape::Rectangle(rect), '<') if rect.text_style.padding > 0 => {
+                    rect.text_style.padding -= 1
+                }
+                (Shape::Rectangle(rect), 'S') => {
+                    rect.text_style.auto_size = rect.text_style.auto_size.next();
+                    rect.auto_size();
+                }
+                (Shape::Rectangle(rect), 'F') => rect.fit_text(true, false),
+                _ => return Ok(()),
+            }
+            if let Shape::Rectangle(rect) = &shape {
+                reattach_arrows(&mut self.document.shapes, &mut self.snapshots, rect);
+            }
+            let mx = self.mutate(StateChange::ReplaceShape(shape));
+            self.push_undo(mx);
+        }
+
+        Ok(())
+    }
+
     pub fn get_cursor_intersection(&self) -> std::io::Result<(Intersection, usize)> {
-        for i in 0..self.shapes.len() {
-            let shape = &self.shapes[i];
-            match shape.get_cursor_intersection() {
-                Ok(Intersection::None) => {}
-                Ok(intersection_type) => {
+        let cursor = self.viewport.cursor_position();
+        for i in 0..self.document.shapes.len() {
+            let shape = &self.document.shapes[i];
+            match shape.get_intersection(&cursor) {
+                Intersection::None => {}
+                intersection_type => {
                     return Ok((intersection_type, i));
                 }
-                _ => {}
             }
         }
 
         Ok((Intersection::None, 0))
     }
 
+    /// Editing keys for the text of a box or the label of an arrow, returns false if the key is
+    /// not one of them
+    pub fn handle_text_key(&mut self, key: &KeyEvent) -> std::io::Result<bool> {
+        let (normal, next) = match &mut self.mode {
+            Mode::Text(edit) => {
+                if !edit.handle_key(key) {
+                    return Ok(false);
+                }
+                edit.target.auto_size();
+                reattach_arrows(&mut self.document.shapes, &mut self.snapshots, &edit.target);
+                let next = edit.target.get_text_cursor_position(edit.cursor);
+                (edit.normal, Some(next))
+            }
+            Mode::Label(edit) => {
+                if !edit.text.handle_key(key) {
+                    return Ok(false);
+                }
+                (
+                    edit.text.normal,
+                    update_label(&mut self.document.shapes, edit),
+                )
+            }
+            _ => return Ok(false),
+        };
+        let style = match normal {
+            true => cursor::SetCursorStyle::SteadyBlock,
+            false => cursor::SetCursorStyle::SteadyBar,
+        };
+        queue!(stdout(), style)?;
+        if let Some(next) = next {
+            self.viewport.set_cursor_position(next);
+        }
+        Ok(true)
+    }
+
+    pub fn handle_char(&mut self, key: char) -> std::io::Result<()> {
+        if let Mode::Command(command) = &mut self.mode {
+            command.push(key);
+        }
+        Ok(())
+    }
+
     pub fn handle_backspace(&mut self) -> std::io::Result<()> {
-        if let Mode::Text(rect) = &mut self.mode {
-            rect.on_backspace()?;
+        if let Mode::Command(command) = &mut self.mode {
+            // Deleting past the start of the command leaves the command line, like in vim
+            if command.pop().is_none() {
+                self.enter_mode(Mode::Normal);
+            }
         }
         Ok(())
     }
 
-    pub fn undo(&mut self) {
-        if let Some(undo) = self.undo_stack.pop() {
-            let redo = self.mutate(undo);
-            self.redo_stack.push(redo);
+    /// Start typing a command after ':'
+    pub fn handle_command_line(&mut self) -> std::io::Result<()> {
+        if let Mode::Normal = self.mode {
+            self.enter_mode(Mode::Command(String::new()));
         }
+        Ok(())
     }
 
-    pub fn redo(&mut self) {
-        if let Some(redo) = self.redo_stack.pop() {
-            let undo = self.mutate(redo);
-            self.undo_stack.push(undo);
+    fn run_command(&mut self, command: Command) {
+        match command {
+            Command::Earlier(HistoryStep::Changes(n)) => self.step_history(-n),
+            Command::Later(HistoryStep::Changes(n)) => self.step_history(n),
+            Command::Earlier(HistoryStep::Seconds(s)) => {
+                self.with_history(|history, state| history.travel(-s, state))
+            }
+            Command::Later(HistoryStep::Seconds(s)) => {
+                self.with_history(|history, state| history.travel(s, state))
+            }
+            Command::Write => {
+                self.save();
+            }
+            Command::Quit { force } => self.quit(force),
+            Command::Export(format) => self.export(&format),
+            Command::WriteQuit => {
+                if self.save() {
+                    self.quit(false);
+                }
+            }
+        }
+    }
+
+    /// Keys that start with 'g' in normal mode, returns false if the key is not part of one
+    pub fn handle_history_key(&mut self, key: char) -> bool {
+        match (self.pending.take(), key) {
+            (None, 'g') => self.pending = Some(key),
+            (Some('g'), '-') => self.step_history(-1),
+            (Some('g'), '+') => self.step_history(1),
+            (Some(_), _) => {}
+            (None, _) => return false,
         }
+        true
+    }
+
+    /// Move through every change in the order they were made, including undone ones
+    fn step_history(&mut self, changes: i64) {
+        self.with_history(|history, state| history.step(changes, state));
+    }
+
+    pub fn undo(&mut self) {
+        self.with_history(|history, state| {
+            history.undo(state);
+        });
+    }
+
+    pub fn redo(&mut self) {
+        self.with_history(|history, state| {
+            history.redo(state);
+        });
+    }
+
+    /// The history changes the state, so it is taken out of the state while it does
+    fn with_history(&mut self, f: impl FnOnce(&mut History, &mut Self)) {
+        let mut history = std::mem::take(&mut self.history);
+        f(&mut history, self);
+        self.history = history;
     }
 }
 
@@ -204,8 +906,15 @@ impl DrawOverlay for State {
         Option<crate::draw::Color>,
     )> {
         let mut overlay_points: Vec<Vec<OverlayPoint>> = vec![];
-        for shape in &self.shapes {
-            if shape.hovered()? {
+        let cursor = self.viewport.cursor_position();
+        let highlighted = match &self.mode {
+            Mode::Move(movement) => movement.shape_ids.clone(),
+            Mode::EditArrow(edit) => vec![edit.shape_id],
+            Mode::Label(edit) => vec![edit.shape_id],
+            _ => vec![],
+        };
+        for shape in &self.document.shapes {
+            if shape.hovered(&cursor) || highlighted.contains(&shape.shape_id()) {
                 overlay_points.push(
                     shape
                         .draw()?
@@ -227,21 +936,366 @@ impl Mutate for State {
     type Mutation = StateChange;
     fn mutate(&mut self, mx: Self::Mutation) -> Self::Mutation {
         match mx {
-            StateChange::DeleteShape(index) => {
-                let removed = self.shapes.remove(index);
-                StateChange::AddShape(removed)
+            StateChange::DeleteShape(shape_id) => {
+                let shapes = &mut self.document.shapes;
+                let Some(index) = shapes.iter().position(|s| s.shape_id() == shape_id) else {
+                    return StateChange::default();
+                };
+                let removed = shapes.remove(index);
+                let before = shapes.get(index).map(|s| s.shape_id());
+                StateChange::InsertShape(before, removed)
+            }
+            StateChange::InsertShape(before, shape) => {
+                let shapes = &mut self.document.shapes;
+                let index = before
+                    .and_then(|id| shapes.iter().position(|s| s.shape_id() == id))
+                    .unwrap_or(shapes.len());
+                let shape_id = shape.shape_id();
+                shapes.insert(index, shape);
+                StateChange::DeleteShape(shape_id)
             }
             StateChange::AddShape(shape) => {
-                self.shapes.push(shape);
-                let index = self.shapes.len() - 1;
-                StateChange::DeleteShape(index)
+                let shape_id = shape.shape_id();
+                self.document.shapes.push(shape);
+                StateChange::DeleteShape(shape_id)
+            }
+            StateChange::MoveShapes(shape_ids, delta) => {
+                translate_shapes(&mut self.document.shapes, &shape_ids, &delta);
+                StateChange::MoveShapes(shape_ids, -delta)
+            }
+            StateChange::ReplaceShape(shape) => {
+                match self
+                    .document
+                    .shapes
+                    .iter_mut()
+                    .find(|s| s.shape_id() == shape.shape_id())
+                {
+                    Some(existing) => StateChange::ReplaceShape(std::mem::replace(existing, shape)),
+                    None => self.mutate(StateChange::AddShape(shape)),
+                }
+            }
+            StateChange::Batch(changes) => {
+                let mut undo: Vec<StateChange> =
+                    changes.into_iter().map(|mx| self.mutate(mx)).collect();
+                undo.reverse();
+                StateChange::Batch(undo)
+            }
+        }
+    }
+}
+
+fn rectangles(shapes: &[Shape]) -> Vec<&Rectangle> {
+    shapes
+        .iter()
+        .filter_map(|s| match s {
+            Shape::Rectangle(r) => Some(r),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Copy the label being typed into its arrow, returns where the cursor goes in the label
+fn update_label(shapes: &mut [Shape], edit: &LabelEdit) -> Option<Vec2<i32>> {
+    let arrow = shapes.iter_mut().find_map(|s| match s {
+        Shape::Arrow(a) if a.shape_id == edit.shape_id => Some(a),
+        _ => None,
+    })?;
+    arrow.label = edit.text.target.clone();
+    arrow.get_label_cursor_position(edit.text.cursor)
+}
+
+/// Re-route every arrow attached to a rectangle that has changed size or position
+fn reattach_arrows(shapes: &mut [Shape], snapshots: &mut Vec<Shape>, rect: &Rectangle) {
+    // The changed rectangle may be being edited, in which case it is not in the list of shapes
+    let rectangles: Vec<Rectangle> = rectangles(shapes)
+        .into_iter()
+        .filter(|r| r.shape_id != rect.shape_id)
+        .chain([rect])
+        .cloned()
+        .collect();
+    let rectangles: Vec<&Rectangle> = rectangles.iter().collect();
+    for shape in shapes.iter_mut() {
+        if let Shape::Arrow(arrow) = shape {
+            if !arrow.is_bound_to(rect.shape_id) {
+                continue;
+            }
+            if !snapshots.iter().any(|s| s.shape_id() == arrow.shape_id) {
+                snapshots.push(Shape::Arrow(arrow.clone()));
+            }
+            match arrow.routed {
+                true => arrow.reroute(&rectangles),
+                false => arrow.follow(rect),
             }
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+fn translate_shapes(shapes: &mut [Shape], shape_ids: &[u32], delta: &Vec2<i32>) {
+    shapes
+        .iter_mut()
+        .filter(|s| shape_ids.contains(&s.shape_id()))
+        .for_each(|s| s.translate(delta));
+}
+
+/// Changes refer to shapes by id rather than by where they are in the list, so that they still
+/// apply to the same shapes when the history is loaded from a file
+#[derive(Serialize, Deserialize, Clone)]
 pub enum StateChange {
-    DeleteShape(usize),
+    DeleteShape(u32),
     AddShape(Shape),
+    /// Put a shape back in front of the shape with the given id, or at the end of the list, which
+    /// is the order shapes are drawn in
+    InsertShape(Option<u32>, Shape),
+    MoveShapes(Vec<u32>, Vec2<i32>),
+    /// Swap in a new version of the shape with the same id
+    ReplaceShape(Shape),
+    /// Several changes that are undone together
+    Batch(Vec<StateChange>),
+}
+
+/// No change at all
+impl Default for StateChange {
+    fn default() -> Self {
+        StateChange::Batch(vec![])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use crate::{
+        components::{arrow::Arrow, rectangle::Rectangle},
+        mode::{LabelEdit, Mode, Movement, TextEdit},
+        mutate::Mutate,
+        shape::Shape,
+        util::Vec2,
+    };
+
+    use super::{State, StateChange};
+
+    /// Three boxes in a row, with room in the terminal to put the cursor inside of any of them
+    fn three_boxes() -> (State, Vec<u32>) {
+        let mut state = State::init();
+        state.viewport.resize(80, 24);
+        for x in 0..3 {
+            let mut rect = Rectangle::new_at(x * 5, 0);
+            rect.width = 4;
+            rect.height = 3;
+            state.document.shapes.push(Shape::Rectangle(rect));
+        }
+        let shape_ids = state.document.shapes.iter().map(|s| s.shape_id()).collect();
+        (state, shape_ids)
+    }
+
+    fn press(state: &mut State, code: KeyCode) {
+        let key = KeyEvent::new(code, KeyModifiers::NONE);
+        assert!(state.handle_text_key(&key).unwrap());
+    }
+
+    #[test]
+    fn should_undo_move() {
+        let mut state = State::init();
+        let rect = Rectangle::new_at(1, 1);
+        let shape_id = rect.shape_id;
+        state.document.shapes.push(Shape::Rectangle(rect));
+
+        let undo = state.mutate(StateChange::MoveShapes(vec![shape_id], (3, -1).into()));
+        state.mutate(undo);
+
+        let Shape::Rectangle(rect) = &state.document.shapes[0] else {
+            panic!("Expected a rectangle");
+        };
+        assert_eq!(rect.origin, Vec2::new(1, 1));
+    }
+
+    #[test]
+    fn should_restore_rerouted_arrows_with_undo() {
+        let mut state = State::init();
+        let mut rect = Rectangle::new_at(2, 0);
+        rect.width = 3;
+        rect.height = 3;
+        let mut arrow = Arrow::init();
+        arrow.points = vec![Vec2::new(0, 1), Vec2::new(2, 1)];
+        arrow.bind(&[&rect]);
+        let original = arrow.points.clone();
+        state.document.shapes.push(Shape::Arrow(arrow));
+
+        rect.origin.x += 2;
+        super::reattach_arrows(&mut state.document.shapes, &mut state.snapshots, &rect);
+        state.add_shape(Shape::Rectangle(rect));
+        state.undo();
+
+        let Shape::Arrow(arrow) = &state.document.shapes[0] else {
+            panic!("Expected an arrow");
+        };
+        assert_eq!(arrow.points, original);
+        assert_eq!(state.document.shapes.len(), 1);
+    }
+
+    #[test]
+    fn should_undo_label_edit_in_one_step() {
+        let mut state = State::init();
+        let mut arrow = Arrow::init();
+        arrow.points = vec![Vec2::new(0, 0), Vec2::new(6, 0)];
+        state.mode = Mode::Label(LabelEdit {
+            shape_id: arrow.shape_id,
+            original: arrow.clone(),
+            text: TextEdit::new(arrow.label.clone()),
+        });
+        state.document.shapes.push(Shape::Arrow(arrow));
+
+        // Typed in the middle of the label, then edited from the normal sub-mode
+        "tp".chars()
+            .for_each(|c| press(&mut state, KeyCode::Char(c)));
+        press(&mut state, KeyCode::Left);
+        press(&mut state, KeyCode::Char('c'));
+        press(&mut state, KeyCode::Esc);
+        press(&mut state, KeyCode::Char('A'));
+        press(&mut state, KeyCode::Char('s'));
+        state.handle_enter().unwrap();
+        let Shape::Arrow(arrow) = &state.document.shapes[0] else {
+            panic!("Expected an arrow");
+        };
+        assert_eq!(arrow.label.text, vec!['t', 'c', 'p', 's']);
+
+        state.undo();
+        let Shape::Arrow(arrow) = &state.document.shapes[0] else {
+            panic!("Expected an arrow");
+        };
+        assert!(arrow.label.text.is_empty());
+    }
+
+    #[test]
+    fn should_put_edited_box_back_on_escape() {
+        let (mut state, shape_ids) = three_boxes();
+        state.viewport.set_cursor_position(Vec2::new(6, 1));
+        state.handle_insert().unwrap();
+        press(&mut state, KeyCode::Char('a'));
+        assert!(matches!(&state.mode, Mode::Text(edit) if edit.target.text == vec!['a']));
+
+        // The first 'esc' only stops typing, cancelling the edit is left to 'CTRL-c'
+        press(&mut state, KeyCode::Esc);
+        assert!(matches!(state.mode, Mode::Text(_)));
+        state.handle_escape().unwrap();
+
+        assert!(state.mode.is_normal());
+        let ids: Vec<u32> = state.document.shapes.iter().map(|s| s.shape_id()).collect();
+        assert_eq!(ids, shape_ids);
+        let Shape::Rectangle(rect) = &state.document.shapes[1] else {
+            panic!("Expected a rectangle");
+        };
+        assert!(rect.text.is_empty());
+    }
+
+    #[test]
+    fn should_move_shapes_back_on_escape() {
+        let mut state = State::init();
+        let rect = Rectangle::new_at(1, 1);
+        let shape_id = rect.shape_id;
+        state.document.shapes.push(Shape::Rectangle(rect));
+        let mut movement = Movement::new(vec![shape_id], Vec2::new(0, 0));
+        let delta = movement.update(&Vec2::new(4, 2));
+        super::translate_shapes(&mut state.document.shapes, &[shape_id], &delta);
+        state.mode = Mode::Move(movement);
+
+        state.handle_escape().unwrap();
+        let Shape::Rectangle(rect) = &state.document.shapes[0] else {
+            panic!("Expected a rectangle");
+        };
+        assert_eq!(rect.origin, Vec2::new(1, 1));
+    }
+
+    #[test]
+    fn should_undo_text_edit_in_place() {
+        let (mut state, shape_ids) = three_boxes();
+        let text = |state: &State| match &state.document.shapes[1] {
+            Shape::Rectangle(rect) => rect.text.clone(),
+            _ => panic!("Expected a rectangle"),
+        };
+
+        state.viewport.set_cursor_position(Vec2::new(6, 1));
+        state.handle_insert().unwrap();
+        press(&mut state, KeyCode::Char('a'));
+        state.handle_enter().unwrap();
+        assert_eq!(text(&state), vec!['a']);
+
+        state.undo();
+        let ids: Vec<u32> = state.document.shapes.iter().map(|s| s.shape_id()).collect();
+        assert_eq!(ids, shape_ids);
+        assert!(text(&state).is_empty());
+
+        state.redo();
+        assert_eq!(text(&state), vec!['a']);
+    }
+
+    #[test]
+    fn should_clear_redo_after_new_change() {
+        let mut state = State::init();
+        state.add_shape(Shape::Rectangle(Rectangle::new_at(0, 0)));
+        state.undo();
+        state.add_shape(Shape::Rectangle(Rectangle::new_at(5, 0)));
+        state.redo();
+        assert_eq!(state.document.shapes.len(), 1);
+    }
+
+    #[test]
+    fn should_undo_delete_in_place() {
+        let (mut state, shape_ids) = three_boxes();
+
+        state.viewport.set_cursor_position(Vec2::new(1, 1));
+        state.handle_delete().unwrap();
+        assert_eq!(state.document.shapes.len(), 2);
+        state.undo();
+        let ids: Vec<u32> = state.document.shapes.iter().map(|s| s.shape_id()).collect();
+        assert_eq!(ids, shape_ids);
+    }
+
+    #[test]
+    fn should_refuse_to_quit_with_unsaved_changes() {
+        let mut state = State::init();
+        state.add_shape(Shape::Rectangle(Rectangle::new_at(0, 0)));
+        assert!(state.is_dirty());
+        state.quit(false);
+        assert!(!state.should_quit);
+        assert!(state.message.as_ref().is_some_and(|m| m.is_error));
+
+        // Undoing back to the saved drawing leaves nothing to save
+        state.undo();
+        assert!(!state.is_dirty());
+        state.quit(false);
+        assert!(state.should_quit);
+    }
+
+    #[test]
+    fn should_report_failed_save() {
+        let mut state = State::init();
+        state.file_name = std::env::temp_dir()
+            .join("traw_missing_directory/drawing.traw")
+            .to_string_lossy()
+            .to_string();
+        state.add_shape(Shape::Rectangle(Rectangle::new_at(0, 0)));
+        assert!(!state.save());
+        assert!(state.is_dirty());
+        assert!(state.message.as_ref().is_some_and(|m| m.is_error));
+    }
+
+    #[test]
+    fn should_recover_shape_being_edited() {
+        let (mut state, shape_ids) = three_boxes();
+        state.viewport.set_cursor_position(Vec2::new(1, 1));
+        state.handle_insert().unwrap();
+        press(&mut state, KeyCode::Char('a'));
+
+        // The box being typed into goes back where it was, with what has been typed so far
+        let document = state.document_to_recover();
+        let ids: Vec<u32> = document.shapes.iter().map(|s| s.shape_id()).collect();
+        assert_eq!(ids, shape_ids);
+        assert!(matches!(&document.shapes[0], Shape::Rectangle(rect) if rect.text == vec!['a']));
+
+        // A recovered drawing has not been saved yet
+        let mut state = State::init();
+        state.recover(document);
+        assert!(state.is_dirty());
+    }
 }
@@ -1,3 +1,5 @@
+pub mod route;
+pub mod text;
 pub mod vec2;
 pub use vec2::Vec2;
 pub mod yank;
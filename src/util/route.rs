@@ -0,0 +1,265 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::{
+    components::rectangle::{Border, Rectangle},
+    util::Vec2,
+};
+
+/// Cost of moving one cell
+const STEP: u32 = 10;
+/// Extra cost of changing direction, so that routes with fewer turns are preferred over routes
+/// that are only slightly shorter
+const TURN: u32 = 50;
+/// How far outside of the two boxes being connected a route is allowed to wander
+const MARGIN: i32 = 10;
+/// Most cells looked at before giving up on finding a route, so that boxes far apart or walled
+/// in cannot stall the editor
+const MAX_EXPANDED: usize = 20_000;
+
+const BORDERS: [Border; 4] = [Border::Top, Border::Right, Border::Bottom, Border::Left];
+
+type Node = (Vec2<i32>, usize);
+
+/// Find the cheapest orthogonal path from the border of one rectangle to the border of another,
+/// going around every rectangle in `obstacles`. The returned points include both border cells.
+pub fn route(
+    source: &Rectangle,
+    target: &Rectangle,
+    obstacles: &[&Rectangle],
+) -> Option<Vec<Vec2<i32>>> {
+    let grid = Grid::new(source, target, obstacles);
+    let is_blocked = |p: &Vec2<i32>| grid.is_blocked(p);
+
+    let mut open = BinaryHeap::new();
+    let mut costs: HashMap<Node, u32> = HashMap::new();
+    let mut parents: HashMap<Node, Node> = HashMap::new();
+    let mut starts: HashMap<Node, Vec2<i32>> = HashMap::new();
+
+    for (direction, border) in BORDERS.iter().enumerate() {
+        for (start, bias) in border_cells(source, border) {
+            let node = (start.clone() + border.normal(), direction);
+            if is_blocked(&node.0) || costs.contains_key(&node) {
+                continue;
+            }
+            let cost = STEP + bias;
+            open.push((
+                Reverse(cost + heuristic(&node.0, target)),
+                cost,
+                node.clone(),
+            ));
+            costs.insert(node.clone(), cost);
+            starts.insert(node, start);
+        }
+    }
+
+    // Of the nodes that look equally good, the one furthest along is tried first, so that open
+    // space is not searched in every order that a route could take through it
+    let mut expanded = 0;
+    while let Some((_, cost, node)) = open.pop() {
+        if costs.get(&node).is_some_and(|c| *c < cost) {
+            continue;
+        }
+        expanded += 1;
+        if expanded > MAX_EXPANDED {
+            return None;
+        }
+        let (position, direction) = &node;
+        if target.border_at(position).is_some() {
+            return Some(build_path(&node, &parents, &starts));
+        }
+        for (next_direction, border) in BORDERS.iter().enumerate() {
+            // Never double back on the path
+            if next_direction == (direction + 2) % 4 {
+                continue;
+            }
+            let next = position.clone() + border.normal();
+            let mut next_cost = cost + STEP;
+            if next_direction != *direction {
+                next_cost += TURN;
+            }
+            match target.border_at(&next) {
+                // Only enter the target straight on, not sliding along its border
+                Some((side, offset)) => {
+                    if side.normal() != -border.normal() {
+                        continue;
+                    }
+                    next_cost += middle_bias(target, &side, offset);
+                }
+                None => {
+                    if is_blocked(&next) {
+                        continue;
+                    }
+                }
+            }
+            let next_node = (next, next_direction);
+            if costs.get(&next_node).is_some_and(|c| *c <= next_cost) {
+                continue;
+            }
+            costs.insert(next_node.clone(), next_cost);
+            parents.insert(next_node.clone(), node.clone());
+            open.push((
+                Reverse(next_cost + heuristic(&next_node.0, target)),
+                next_cost,
+                next_node,
+            ));
+        }
+    }
+
+    None
+}
+
+fn build_path(
+    end: &Node,
+    parents: &HashMap<Node, Node>,
+    starts: &HashMap<Node, Vec2<i32>>,
+) -> Vec<Vec2<i32>> {
+    let mut points = vec![end.0.clone()];
+    let mut current = end;
+    while let Some(parent) = parents.get(current) {
+        points.push(parent.0.clone());
+        current = parent;
+    }
+    if let Some(start) = starts.get(current) {
+        points.push(start.clone());
+    }
+    points.reverse();
+    points
+}
+
+/// Every cell of a border that an arrow can attach to, with a small cost for being further from
+/// the middle so that routes prefer to leave from the center of a side
+fn border_cells(rect: &Rectangle, border: &Border) -> Vec<(Vec2<i32>, u32)> {
+    let length = match border {
+        Border::Top | Border::Bottom => rect.width,
+        Border::Left | Border::Right => rect.height,
+    };
+    (1..length - 1)
+        .map(|offset| {
+            (
+                rect.border_position(border, offset),
+                middle_bias(rect, border, offset),
+            )
+        })
+        .collect()
+}
+
+fn middle_bias(rect: &Rectangle, border: &Border, offset: i32) -> u32 {
+    let length = match border {
+        Border::Top | Border::Bottom => rect.width,
+        Border::Left | Border::Right => rect.height,
+    };
+    (offset * 2 - (length - 1)).unsigned_abs()
+}
+
+/// Manhattan distance to the nearest cell next to the target, plus a turn when the target is off
+/// to the side in both directions, which never overestimates
+fn heuristic(position: &Vec2<i32>, target: &Rectangle) -> u32 {
+    let distance = |p: i32, start: i32, end: i32| (start - p).max(p - end).max(0) as u32;
+    let dx = distance(position.x, target.x - 1, target.x + target.width);
+    let dy = distance(position.y, target.y - 1, target.y + target.height);
+    let turn = if dx > 0 && dy > 0 { TURN } else { 0 };
+    (dx + dy) * STEP + turn
+}
+
+/// The cells a route may go through, worked out once for the area around the two boxes instead
+/// of checking every rectangle for every cell that is looked at
+struct Grid {
+    min: Vec2<i32>,
+    width: i32,
+    height: i32,
+    blocked: Vec<bool>,
+}
+
+impl Grid {
+    fn new(source: &Rectangle, target: &Rectangle, obstacles: &[&Rectangle]) -> Self {
+        let min = Vec2::new(
+            source.x.min(target.x) - MARGIN,
+            source.y.min(target.y) - MARGIN,
+        );
+        let max = Vec2::new(
+            (source.x + source.width).max(target.x + target.width) + MARGIN,
+            (source.y + source.height).max(target.y + target.height) + MARGIN,
+        );
+        let mut grid = Self {
+            width: max.x - min.x,
+            height: max.y - min.y,
+            blocked: vec![],
+            min,
+        };
+        grid.blocked = vec![false; (grid.width * grid.height) as usize];
+        for r in obstacles.iter().copied().chain([source, target]) {
+            let x_range = r.x.max(grid.min.x)..(r.x + r.width).min(grid.min.x + grid.width);
+            for y in r.y.max(grid.min.y)..(r.y + r.height).min(grid.min.y + grid.height) {
+                for x in x_range.clone() {
+                    let index = grid.index(&Vec2::new(x, y));
+                    grid.blocked[index] = true;
+                }
+            }
+        }
+        grid
+    }
+
+    fn index(&self, p: &Vec2<i32>) -> usize {
+        ((p.y - self.min.y) * self.width + p.x - self.min.x) as usize
+    }
+
+    fn is_blocked(&self, p: &Vec2<i32>) -> bool {
+        let inside = p.x >= self.min.x
+            && p.x < self.min.x + self.width
+            && p.y >= self.min.y
+            && p.y < self.min.y + self.height;
+        !inside || self.blocked[self.index(p)]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{components::rectangle::Rectangle, util::Vec2};
+
+    use super::route;
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rectangle {
+        let mut rect = Rectangle::new_at(x, y);
+        rect.width = width;
+        rect.height = height;
+        rect
+    }
+
+    #[test]
+    fn should_route_straight_between_aligned_boxes() {
+        let source = rect(0, 0, 5, 5);
+        let target = rect(10, 0, 5, 5);
+        let points = route(&source, &target, &[]).unwrap();
+        assert_eq!(points.first(), Some(&Vec2::new(4, 2)));
+        assert_eq!(points.last(), Some(&Vec2::new(10, 2)));
+        assert!(points.iter().all(|p| p.y == 2));
+    }
+
+    #[test]
+    fn should_route_around_obstacles() {
+        let source = rect(0, 0, 5, 5);
+        let target = rect(16, 0, 5, 5);
+        let obstacle = rect(8, -3, 5, 11);
+        let points = route(&source, &target, &[&obstacle]).unwrap();
+        assert!(target.border_at(points.last().unwrap()).is_some());
+        assert!(points
+            .iter()
+            .all(|p| p.x < 8 || p.x > 12 || p.y < -3 || p.y > 7));
+        assert!(points.windows(2).all(|w| {
+            let d = w[1].clone() - w[0].clone();
+            d.x.abs() + d.y.abs() == 1
+        }));
+    }
+
+    #[test]
+    fn should_route_between_boxes_far_apart() {
+        let source = rect(0, 0, 5, 5);
+        let target = rect(400, 300, 5, 5);
+        let points = route(&source, &target, &[]).unwrap();
+        assert!(source.border_at(points.first().unwrap()).is_some());
+        assert!(target.border_at(points.last().unwrap()).is_some());
+    }
+}
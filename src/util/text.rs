@@ -0,0 +1,199 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Deserializer, Serializer};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Grapheme clusters in the text, as ranges of indices into it, with the number of cells each
+/// one takes up on screen
+pub fn graphemes(text: &[char]) -> Vec<(Range<usize>, usize)> {
+    let text: String = text.iter().collect();
+    let mut start = 0;
+    text.graphemes(true)
+        .map(|g| {
+            let end = start + g.chars().count();
+            let range = start..end;
+            start = end;
+            (range, g.width())
+        })
+        .collect()
+}
+
+/// Number of cells the text takes up on screen
+pub fn display_width(text: &[char]) -> usize {
+    text.iter().collect::<String>().width()
+}
+
+/// Cell offset of every character from the start of the text, then the offset just after the end.
+/// Characters in the same grapheme cluster, like a letter and its accent, share a cell
+pub fn columns(text: &[char]) -> Vec<usize> {
+    let mut columns = Vec::with_capacity(text.len() + 1);
+    let mut x = 0;
+    for (range, width) in graphemes(text) {
+        columns.extend(range.map(|_| x));
+        x += width;
+    }
+    columns.push(x);
+    columns
+}
+
+/// Start of the grapheme cluster before `i`
+pub fn prev_grapheme(text: &[char], i: usize) -> usize {
+    graphemes(text)
+        .into_iter()
+        .map(|(range, _)| range.start)
+        .take_while(|start| *start < i)
+        .last()
+        .unwrap_or(0)
+}
+
+/// End of the grapheme cluster that starts at `i`
+pub fn next_grapheme(text: &[char], i: usize) -> usize {
+    graphemes(text)
+        .into_iter()
+        .map(|(range, _)| range.end)
+        .find(|end| *end > i)
+        .unwrap_or(text.len())
+}
+
+/// Text that can be typed into with a [`TextEdit`](crate::mode::TextEdit). Text is on a single
+/// line unless the line methods are given
+pub trait EditText {
+    fn text(&self) -> &Vec<char>;
+
+    fn text_mut(&mut self) -> &mut Vec<char>;
+
+    /// Whether 'ALT-enter' starts a new line
+    fn is_multiline(&self) -> bool {
+        false
+    }
+
+    /// Indices of the text on the same line as `index`
+    fn line_range(&self, _index: usize) -> Range<usize> {
+        0..self.text().len()
+    }
+
+    /// Index in the text that is closest to being directly above or below `index`, `rows` lines
+    /// away
+    fn move_vertically(&self, index: usize, _rows: i32) -> usize {
+        index
+    }
+}
+
+/// Text is kept as characters so that it can be edited by index, but saved as a string which is
+/// much easier to read in a file
+pub fn serialize_chars<S: Serializer>(text: &[char], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&text.iter().collect::<String>())
+}
+
+/// Files saved before text was a string have a list of characters
+pub fn deserialize_chars<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<char>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Text {
+        String(String),
+        Chars(Vec<char>),
+    }
+    Ok(match Text::deserialize(deserializer)? {
+        Text::String(text) => text.chars().collect(),
+        Text::Chars(chars) => chars,
+    })
+}
+
+/// Kinds of characters that make up a word, a run of the same kind is one word in the same way as
+/// vim treats them
+#[derive(PartialEq)]
+enum Class {
+    Space,
+    Word,
+    Punctuation,
+}
+
+fn class(c: char) -> Class {
+    if c.is_whitespace() {
+        Class::Space
+    } else if c.is_alphanumeric() || c == '_' {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+/// End of the run of characters of the same kind as the one at `i`, exclusive
+pub fn run_end(text: &[char], i: usize) -> usize {
+    let Some(c) = text.get(i) else {
+        return text.len();
+    };
+    let kind = class(*c);
+    (i..text.len())
+        .find(|j| class(text[*j]) != kind)
+        .unwrap_or(text.len())
+}
+
+/// Start of the next word after `i`, like vim's `w`
+pub fn next_word_start(text: &[char], i: usize) -> usize {
+    let mut i = match text.get(i) {
+        Some(c) if class(*c) != Class::Space => run_end(text, i),
+        _ => i,
+    };
+    while i < text.len() && class(text[i]) == Class::Space {
+        i += 1;
+    }
+    i.min(text.len())
+}
+
+/// Start of the word before `i`, like vim's `b`
+pub fn prev_word_start(text: &[char], i: usize) -> usize {
+    let mut i = i.min(text.len());
+    while i > 0 && class(text[i - 1]) == Class::Space {
+        i -= 1;
+    }
+    if let Some(c) = i.checked_sub(1).map(|j| class(text[j])) {
+        while i > 0 && class(text[i - 1]) == c {
+            i -= 1;
+        }
+    }
+    i
+}
+
+/// Last character of the word after `i`, like vim's `e`
+pub fn word_end(text: &[char], i: usize) -> usize {
+    let mut i = i + 1;
+    while i < text.len() && class(text[i]) == Class::Space {
+        i += 1;
+    }
+    if i >= text.len() {
+        return text.len().saturating_sub(1);
+    }
+    run_end(text, i) - 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        columns, display_width, next_grapheme, next_word_start, prev_grapheme, prev_word_start,
+        word_end,
+    };
+
+    #[test]
+    fn should_measure_wide_and_combining_characters() {
+        let text: Vec<char> = "日本e\u{301}x".chars().collect();
+        assert_eq!(display_width(&text), 6);
+        assert_eq!(columns(&text), vec![0, 2, 4, 4, 5, 6]);
+        assert_eq!(next_grapheme(&text, 2), 4);
+        assert_eq!(prev_grapheme(&text, 4), 2);
+    }
+
+    #[test]
+    fn should_move_between_words() {
+        let text: Vec<char> = "foo.bar  baz".chars().collect();
+        assert_eq!(next_word_start(&text, 0), 3);
+        assert_eq!(next_word_start(&text, 4), 9);
+        assert_eq!(prev_word_start(&text, 9), 4);
+        assert_eq!(prev_word_start(&text, 3), 0);
+        assert_eq!(word_end(&text, 4), 6);
+        assert_eq!(word_end(&text, 6), 11);
+    }
+}
@@ -1,25 +1,86 @@
+use std::io::{Error, ErrorKind, Result};
+
 use cli_clipboard::{ClipboardContext, ClipboardProvider};
 
 use crate::{
-    mode::Selection,
-    renderer::{self, Renderer},
+    components::intersections::Intersections,
+    draw::Draw,
+    mode::{Mode, Selection},
+    renderer::Renderer,
+    state::State,
+    util::Vec2,
 };
 
-pub fn handle_yank(renderer: &Renderer, selection: &Selection) {
+/// Copy the shapes inside of the selection to the clipboard, when there is one
+pub fn handle_yank(state: &State) -> Result<()> {
+    let Mode::Select(selection) = &state.mode else {
+        return Ok(());
+    };
+    let content = yanked_text(state, selection)?;
     let mut ctx = ClipboardContext::new().unwrap();
-    let mut content = vec![];
+    ctx.set_contents(content).unwrap();
+    Ok(())
+}
+
+/// The shapes inside of the selection as text. The selection is drawn on its own, so the parts of
+/// it that are scrolled off screen are copied too
+fn yanked_text(state: &State, selection: &Selection) -> Result<String> {
+    let size = |n: i32| {
+        u16::try_from(n)
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "Selection is too large to copy"))
+    };
+    let mut renderer = Renderer::new(size(selection.width)?, size(selection.height)?);
+    let origin = Vec2::new(selection.x, selection.y);
+    renderer.render_offscreen(&origin, |r| {
+        for shape in &state.document.shapes {
+            r.render(shape.draw()?, Some(shape.shape_id()))?;
+        }
+        r.render(Intersections::new(state).draw()?, None)
+    })?;
+
+    let mut content = String::new();
     for row in 0..selection.height {
         for col in 0..selection.width {
-            let x = col + selection.x;
-            let y = row + selection.y;
-            let cell = &renderer.state[x as usize][y as usize];
+            let Some(cell) = renderer.get_cell(&Vec2::new(col + selection.x, row + selection.y))
+            else {
+                continue;
+            };
             if cell.shape_id.is_none() {
                 // Only output drawn shapes, no background or other characters
                 continue;
             }
-            content.push(cell.character);
+            content.push_str(&cell.text());
         }
         content.push('\n');
     }
-    ctx.set_contents(content.iter().collect()).unwrap();
+    Ok(content)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        components::rectangle::Rectangle, document::Document, mode::Selection, shape::Shape,
+        state::State,
+    };
+
+    use super::yanked_text;
+
+    #[test]
+    fn should_yank_shapes_outside_of_the_screen() {
+        let mut rect = Rectangle::new_at(1000, 1000);
+        rect.width = 3;
+        rect.height = 3;
+        let state = State::open(Document {
+            shapes: vec![Shape::Rectangle(rect)],
+            ..Default::default()
+        });
+        let selection = Selection {
+            x: 1000,
+            y: 1000,
+            width: 3,
+            height: 3,
+        };
+
+        assert_eq!(yanked_text(&state, &selection).unwrap(), "╭─╮\n│ │\n╰─╯\n");
+    }
 }
@@ -0,0 +1,118 @@
+use crate::{
+    cursor::{cursor_position, set_position},
+    util::Vec2,
+};
+
+/// The terminal is a window onto a larger canvas. Shapes are stored in canvas coordinates, and
+/// the viewport offset is the canvas position that is drawn in the top left cell of the screen.
+#[derive(Debug, Default)]
+pub struct Viewport {
+    pub offset: Vec2<i32>,
+    width: u16,
+    height: u16,
+}
+
+impl Viewport {
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.width = width;
+        self.height = height;
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    /// Where the terminal cursor is on the canvas
+    pub fn cursor_position(&self) -> Vec2<i32> {
+        self.to_canvas(cursor_position())
+    }
+
+    /// Move the terminal cursor to a canvas position, scrolling if it is outside of the view
+    pub fn set_cursor_position(&mut self, position: Vec2<i32>) {
+        self.scroll_into_view(&position);
+        if let Some(screen) = self.to_screen(&position) {
+            set_position(screen);
+        }
+    }
+
+    pub fn to_canvas(&self, screen: Vec2<u16>) -> Vec2<i32> {
+        Vec2::<i32>::from(screen) + self.offset.clone()
+    }
+
+    /// Screen position of a canvas position, if it is currently visible
+    pub fn to_screen(&self, canvas: &Vec2<i32>) -> Option<Vec2<u16>> {
+        let x = canvas.x - self.offset.x;
+        let y = canvas.y - self.offset.y;
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        Some((x, y).into())
+    }
+
+    /// Scroll the view by the given amount, keeping the cursor in the same place on screen
+    pub fn pan(&mut self, delta: Vec2<i32>) {
+        self.offset = self.offset.clone() + delta;
+    }
+
+    /// Scroll so that the cursor is in the middle of the screen
+    pub fn center_on_cursor(&mut self) {
+        let position = self.cursor_position();
+        let center = Vec2::new(self.width as i32 / 2, self.height as i32 / 2);
+        self.offset = position.clone() - center;
+        self.set_cursor_position(position);
+    }
+
+    /// Scroll by the smallest amount that makes the canvas position visible
+    fn scroll_into_view(&mut self, position: &Vec2<i32>) {
+        let right = self.offset.x + self.width as i32 - 1;
+        let bottom = self.offset.y + self.height as i32 - 1;
+        if position.x < self.offset.x {
+            self.offset.x = position.x;
+        } else if position.x > right {
+            self.offset.x += position.x - right;
+        }
+        if position.y < self.offset.y {
+            self.offset.y = position.y;
+        } else if position.y > bottom {
+            self.offset.y += position.y - bottom;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::Vec2;
+
+    use super::Viewport;
+
+    fn viewport() -> Viewport {
+        let mut viewport = Viewport::default();
+        viewport.resize(10, 10);
+        viewport
+    }
+
+    #[test]
+    fn should_map_canvas_to_screen() {
+        let mut viewport = viewport();
+        viewport.pan((5, -5).into());
+        assert_eq!(viewport.to_screen(&(5, -5).into()), Some(Vec2::new(0, 0)));
+        assert_eq!(viewport.to_canvas((2, 3).into()), Vec2::new(7, -2));
+    }
+
+    #[test]
+    fn should_clip_positions_outside_of_view() {
+        let viewport = viewport();
+        assert_eq!(viewport.to_screen(&(-1, 0).into()), None);
+        assert_eq!(viewport.to_screen(&(10, 0).into()), None);
+        assert_eq!(viewport.to_screen(&(0, 10).into()), None);
+    }
+
+    #[test]
+    fn should_scroll_position_into_view() {
+        let mut viewport = viewport();
+        viewport.scroll_into_view(&(12, -3).into());
+        assert_eq!(viewport.offset, Vec2::new(3, -3));
+        viewport.scroll_into_view(&(5, 5).into());
+        assert_eq!(viewport.offset, Vec2::new(3, -3));
+    }
+}